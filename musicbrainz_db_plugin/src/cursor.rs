@@ -0,0 +1,37 @@
+use base64::{engine::general_purpose, Engine as _};
+
+/// The state needed to resume a `search_release` page: the original query
+/// and limit (so the client never has to resend them) plus the last-seen
+/// release id to resume after. Encoded as base64 so it's opaque to callers,
+/// even though it's just plain text underneath.
+pub struct Cursor {
+    pub name: String,
+    pub limit: i64,
+    pub last_id: i64,
+}
+
+const FIELD_SEP: char = '\u{1f}';
+
+impl Cursor {
+    pub fn encode(&self) -> String {
+        let raw = format!(
+            "{}{FIELD_SEP}{}{FIELD_SEP}{}",
+            self.name, self.limit, self.last_id
+        );
+        general_purpose::STANDARD.encode(raw)
+    }
+
+    pub fn decode(token: &str) -> Option<Self> {
+        let raw = general_purpose::STANDARD.decode(token).ok()?;
+        let raw = String::from_utf8(raw).ok()?;
+        let mut fields = raw.split(FIELD_SEP);
+        let name = fields.next()?.to_string();
+        let limit = fields.next()?.parse().ok()?;
+        let last_id = fields.next()?.parse().ok()?;
+        Some(Self {
+            name,
+            limit,
+            last_id,
+        })
+    }
+}
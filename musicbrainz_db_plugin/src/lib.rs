@@ -1,20 +1,73 @@
 use search_proto::search_client::SearchClient;
-use search_proto::SearchReleaseRequest;
+use search_proto::search_release_response::Result as SearchResult;
+use search_proto::{
+    Release, SearchReleaseNextRequest, SearchReleaseRequest, SearchReleaseResponse,
+};
 use tonic::transport::Channel;
 
+pub mod retry;
 pub mod search_proto {
     tonic::include_proto!("search");
 }
 
+use retry::{connect_with_backoff, is_transient_transport_error, BackoffConfig};
+
 pub async fn create_client(
 ) -> Result<SearchClient<Channel>, Box<dyn std::error::Error + Send + Sync>> {
-    Ok(SearchClient::connect("http://[::1]:50051").await?)
+    let channel = connect_with_backoff(
+        BackoffConfig::default(),
+        || Channel::from_static("http://[::1]:50051").connect(),
+        is_transient_transport_error,
+    )
+    .await?;
+    Ok(SearchClient::new(channel))
+}
+
+/// Outcome of a release search, mirroring `SearchReleaseResponse`'s oneof:
+/// a successful page of results (possibly with zero matches, plus a
+/// `next_cursor` to keep paging), a recoverable failure the caller can
+/// retry, or a fatal error meaning the service itself is broken.
+#[derive(Debug)]
+pub enum SearchOutcome {
+    Success {
+        releases: Vec<Release>,
+        total_count: i64,
+        next_cursor: Option<String>,
+    },
+    Failure(String),
+    Fatal(String),
+}
+
+fn outcome_from_response(response: SearchReleaseResponse) -> SearchOutcome {
+    match response.result {
+        Some(SearchResult::Success(success)) => SearchOutcome::Success {
+            releases: success.releases,
+            total_count: success.total_count,
+            next_cursor: success.next_cursor,
+        },
+        Some(SearchResult::Failure(failure)) => SearchOutcome::Failure(failure.message),
+        Some(SearchResult::Fatal(fatal)) => SearchOutcome::Fatal(fatal.message),
+        None => SearchOutcome::Fatal("server returned no result".to_string()),
+    }
+}
+
+pub async fn search(
+    client: &mut SearchClient<Channel>,
+    query: String,
+    limit: i32,
+) -> Result<SearchOutcome, tonic::Status> {
+    let request = tonic::Request::new(SearchReleaseRequest { name: query, limit });
+    let response = client.search_release(request).await?.into_inner();
+    Ok(outcome_from_response(response))
 }
 
-pub async fn search(client: &mut SearchClient<Channel>, query: String) {
-    let request = tonic::Request::new(SearchReleaseRequest {
-        name: "kuroi uta".to_string(),
-    });
-    let x = client.search_release(request).await;
-    println!("{:?}", x)
+/// Fetches the next page of a prior `search` call using the `next_cursor`
+/// it returned, without re-sending the original query.
+pub async fn search_next(
+    client: &mut SearchClient<Channel>,
+    cursor: String,
+) -> Result<SearchOutcome, tonic::Status> {
+    let request = tonic::Request::new(SearchReleaseNextRequest { cursor });
+    let response = client.search_release_next(request).await?.into_inner();
+    Ok(outcome_from_response(response))
 }
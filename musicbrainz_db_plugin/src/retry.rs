@@ -0,0 +1,92 @@
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+/// Exponential backoff schedule for [`connect_with_backoff`]. Delays start
+/// at `initial_delay`, double each retry up to `max_delay`, and retrying
+/// stops once `deadline` has elapsed since the first attempt.
+#[derive(Debug, Clone, Copy)]
+pub struct BackoffConfig {
+    pub initial_delay: Duration,
+    pub max_delay: Duration,
+    pub deadline: Duration,
+}
+
+impl Default for BackoffConfig {
+    fn default() -> Self {
+        Self {
+            initial_delay: Duration::from_millis(200),
+            max_delay: Duration::from_secs(30),
+            deadline: Duration::from_secs(120),
+        }
+    }
+}
+
+/// +/-15% jitter so many clients reconnecting at once don't all retry in
+/// lockstep. Good enough for spacing out retries; not used anywhere that
+/// needs real randomness.
+fn jittered(delay: Duration) -> Duration {
+    let nanos = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.subsec_nanos())
+        .unwrap_or(0);
+    let factor = 0.85 + (nanos % 1000) as f64 / 1000.0 * 0.3;
+    delay.mul_f64(factor)
+}
+
+/// Retries `connect` with exponential backoff while `is_transient` says the
+/// error is worth retrying. Returns the first permanent error, or the last
+/// transient one once `config.deadline` has elapsed.
+pub async fn connect_with_backoff<T, E, Fut>(
+    config: BackoffConfig,
+    mut connect: impl FnMut() -> Fut,
+    is_transient: impl Fn(&E) -> bool,
+) -> Result<T, E>
+where
+    Fut: std::future::Future<Output = Result<T, E>>,
+{
+    let start = Instant::now();
+    let mut delay = config.initial_delay;
+    loop {
+        match connect().await {
+            Ok(value) => return Ok(value),
+            Err(err) if is_transient(&err) && start.elapsed() < config.deadline => {
+                tokio::time::sleep(jittered(delay).min(config.max_delay)).await;
+                delay = (delay * 2).min(config.max_delay);
+            }
+            Err(err) => return Err(err),
+        }
+    }
+}
+
+/// Treats a dropped/refused/reset TCP connection as transient; anything
+/// else (auth failure, bad query, schema mismatch) is permanent.
+pub fn is_transient_sqlx_error(err: &sqlx::Error) -> bool {
+    matches!(
+        err,
+        sqlx::Error::Io(io_err) if matches!(
+            io_err.kind(),
+            std::io::ErrorKind::ConnectionRefused
+                | std::io::ErrorKind::ConnectionReset
+                | std::io::ErrorKind::ConnectionAborted
+        )
+    )
+}
+
+/// Walks a tonic transport error's source chain looking for the underlying
+/// `io::Error` that hyper/h2 wrap connect failures in, and classifies it the
+/// same way as [`is_transient_sqlx_error`].
+pub fn is_transient_transport_error(err: &tonic::transport::Error) -> bool {
+    use std::error::Error;
+    let mut source = err.source();
+    while let Some(current) = source {
+        if let Some(io_err) = current.downcast_ref::<std::io::Error>() {
+            return matches!(
+                io_err.kind(),
+                std::io::ErrorKind::ConnectionRefused
+                    | std::io::ErrorKind::ConnectionReset
+                    | std::io::ErrorKind::ConnectionAborted
+            );
+        }
+        source = current.source();
+    }
+    false
+}
@@ -1,23 +1,105 @@
-use std::borrow::Borrow;
-use std::sync::{Arc, Mutex};
+use std::sync::Arc;
 
 use sqlx::postgres::PgPoolOptions;
 use sqlx::{Pool, Postgres};
-use tonic::Code;
 use tonic::{transport::Server, Request, Response, Status};
 
+use search_proto::search_release_response::Result as SearchResult;
 use search_proto::search_server::{Search, SearchServer};
-use search_proto::{SearchReleaseRequest, SearchReleaseResponse};
+use search_proto::{
+    Release, SearchFailure, SearchFatal, SearchReleaseNextRequest, SearchReleaseRequest,
+    SearchReleaseResponse, SearchSuccess,
+};
 
 pub mod search_proto {
     tonic::include_proto!("search");
 }
 
+use musicbrainz_db_client::retry::{connect_with_backoff, is_transient_sqlx_error, BackoffConfig};
+
+mod cursor;
+use cursor::Cursor;
+
+const DEFAULT_LIMIT: i64 = 10;
+
 #[derive(Debug)]
 struct MySearch {
     pool: Arc<Pool<Postgres>>,
 }
 
+/// Connection hiccups are recoverable (the caller can retry); anything else
+/// — a missing table, a bad column, a broken migration — means the service
+/// itself is unhealthy and retrying won't help.
+fn classify_query_error(err: &sqlx::Error) -> SearchResult {
+    let message = err.to_string();
+    if is_transient_sqlx_error(err) {
+        SearchResult::Failure(SearchFailure { message })
+    } else {
+        SearchResult::Fatal(SearchFatal { message })
+    }
+}
+
+impl MySearch {
+    /// Runs one page of `name ILIKE` starting after `after_id`, ordered by
+    /// id so the cursor has a stable, monotonic column to resume from.
+    /// Fetches one extra row to tell whether a `next_cursor` is needed
+    /// without a second round trip.
+    async fn run_search(
+        &self,
+        name: &str,
+        limit: i64,
+        after_id: i64,
+    ) -> Result<SearchResult, sqlx::Error> {
+        let pattern = format!("%{name}%");
+        let mut rows = sqlx::query!(
+            "SELECT id, name, artist_credit, date FROM musicbrainz.release \
+             WHERE name ILIKE $1 AND id > $2 ORDER BY id LIMIT $3",
+            pattern,
+            after_id,
+            limit + 1,
+        )
+        .fetch_all(self.pool.as_ref())
+        .await?;
+
+        let total_count = sqlx::query!(
+            "SELECT COUNT(*) AS count FROM musicbrainz.release WHERE name ILIKE $1",
+            pattern
+        )
+        .fetch_one(self.pool.as_ref())
+        .await?
+        .count
+        .unwrap_or(0);
+
+        let has_more = rows.len() as i64 > limit;
+        rows.truncate(limit as usize);
+
+        let next_cursor = has_more.then(|| {
+            Cursor {
+                name: name.to_string(),
+                limit,
+                last_id: rows.last().map(|row| row.id).unwrap_or(after_id),
+            }
+            .encode()
+        });
+
+        let releases = rows
+            .into_iter()
+            .map(|row| Release {
+                id: row.id,
+                name: row.name,
+                artist_credit: row.artist_credit,
+                date: row.date,
+            })
+            .collect();
+
+        Ok(SearchResult::Success(SearchSuccess {
+            releases,
+            total_count,
+            next_cursor,
+        }))
+    }
+}
+
 #[tonic::async_trait]
 impl Search for MySearch {
     async fn search_release(
@@ -26,16 +108,41 @@ impl Search for MySearch {
     ) -> Result<Response<SearchReleaseResponse>, Status> {
         println!("Got a request: {:?}", request);
 
-        let result = sqlx::query!("SELECT * FROM musicbrainz.release LIMIT 10")
-            .fetch_all(self.pool.borrow())
-            .await
-            .map_err(|_| Status::new(Code::Internal, "Failed to query database"))?;
+        let req = request.get_ref();
+        let limit = if req.limit > 0 {
+            req.limit as i64
+        } else {
+            DEFAULT_LIMIT
+        };
+
+        let result = match self.run_search(&req.name, limit, 0).await {
+            Ok(result) => result,
+            Err(err) => classify_query_error(&err),
+        };
 
-        println!("{result:?}");
+        Ok(Response::new(SearchReleaseResponse {
+            result: Some(result),
+        }))
+    }
+
+    async fn search_release_next(
+        &self,
+        request: Request<SearchReleaseNextRequest>,
+    ) -> Result<Response<SearchReleaseResponse>, Status> {
+        let cursor = Cursor::decode(&request.get_ref().cursor)
+            .ok_or_else(|| Status::invalid_argument("malformed cursor"))?;
 
-        let reply = SearchReleaseResponse { result_count: 1 };
+        let result = match self
+            .run_search(&cursor.name, cursor.limit, cursor.last_id)
+            .await
+        {
+            Ok(result) => result,
+            Err(err) => classify_query_error(&err),
+        };
 
-        Ok(Response::new(reply))
+        Ok(Response::new(SearchReleaseResponse {
+            result: Some(result),
+        }))
     }
 }
 
@@ -44,10 +151,16 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
     let addr = "[::1]:50051".parse()?;
     let database_url = std::env::var("DATABASE_URL").expect("Env `DATABASE_URL` not set!");
 
-    let pool = PgPoolOptions::new()
-        .max_connections(5)
-        .connect(&database_url)
-        .await?;
+    let pool = connect_with_backoff(
+        BackoffConfig::default(),
+        || {
+            PgPoolOptions::new()
+                .max_connections(5)
+                .connect(&database_url)
+        },
+        is_transient_sqlx_error,
+    )
+    .await?;
 
     let search = MySearch {
         pool: Arc::new(pool),
@@ -44,4 +44,9 @@ pub enum Commands {
         #[arg(short, long, default_value_t, value_enum)]
         service: SearchService,
     },
+    /// Rescan a library directory, only (re)parsing files that changed
+    /// since the last scan and pruning ones that disappeared
+    Reindex {
+        path: String,
+    },
 }
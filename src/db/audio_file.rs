@@ -1,8 +1,12 @@
 use sqlx::{prelude::FromRow, Executor, Sqlite};
 
 use super::{
+    cue_sheet::{CueIndex, CueSheet, CueTrack},
+    opus::OpusMeta,
     padding::Padding,
-    picture::Picture,
+    picture::{Picture, VorbisBlob},
+    seek_table::SeekPoint,
+    stream_info::StreamInfo,
     vorbis::{VorbisComment, VorbisMeta},
 };
 
@@ -12,6 +16,27 @@ pub struct AudioFileMeta {
     pub comments: Vec<(VorbisMeta, Vec<VorbisComment>)>,
     pub pictures: Vec<Picture>,
     pub paddings: Vec<Padding>,
+    /// deduplicated binary blobs (currently just embedded cover art)
+    /// referenced by `Picture::blob_hash`
+    pub blobs: Vec<VorbisBlob>,
+    /// the `OpusHead` identification header, only present for Opus streams
+    pub opus: Option<OpusMeta>,
+    /// the FLAC `STREAMINFO` block, only present for FLAC streams (native or
+    /// Ogg-encapsulated)
+    pub stream_info: Option<StreamInfo>,
+    /// seek points from a FLAC SEEKTABLE block, if one was present
+    pub seek_points: Vec<SeekPoint>,
+    /// a FLAC CUESHEET block, if one was present, paired with its tracks
+    /// and each track's index points
+    pub cue_sheet: Option<(CueSheet, Vec<(CueTrack, Vec<CueIndex>)>)>,
+}
+
+/// every file sharing a given audio payload hash, i.e. the same recording
+/// saved under different tags/paths
+#[derive(Debug, Clone)]
+pub struct DuplicateGroup {
+    pub audio_hash: String,
+    pub paths: Vec<String>,
 }
 
 #[derive(Debug, Clone, FromRow)]
@@ -20,6 +45,19 @@ pub struct AudioFile {
     pub path: String,
     pub name: String,
     pub format: Option<String>,
+    /// last-modified time (unix seconds) at the time this row was written,
+    /// used by the reindex subsystem to detect changed files without
+    /// reparsing everything
+    pub mtime: Option<i64>,
+    /// file size in bytes at the time this row was written
+    pub size: Option<i64>,
+    /// blake3 digest of the decoded audio payload, tags excluded (see
+    /// `formats::audio_hash`); `None` until the file has been scanned.
+    /// Stable across retagging a native FLAC file, so two files with this
+    /// hash in common are the same recording saved with different tags. Ogg
+    /// page headers are part of the hashed bytes, so retagging an Ogg file
+    /// changes it.
+    pub audio_hash: Option<String>,
 }
 
 impl AudioFile {
@@ -46,12 +84,32 @@ impl AudioFile {
         }
         let pictures = Picture::from_file_id(id, pool).await?;
         let paddings = Padding::from_file_id(id, pool).await?;
+        let opus = OpusMeta::from_file_id(id, pool).await?;
+        let stream_info = StreamInfo::from_file_id(id, pool).await?;
+        let seek_points = SeekPoint::from_file_id(id, pool).await?;
+        let cue_sheet = match CueSheet::from_file_id(id, pool).await? {
+            Some(sheet) => {
+                let tracks = CueTrack::from_cue_sheet_id(sheet.id.unwrap(), pool).await?;
+                let mut full_tracks = Vec::with_capacity(tracks.len());
+                for track in tracks {
+                    let indices = CueIndex::from_cue_track_id(track.id.unwrap(), pool).await?;
+                    full_tracks.push((track, indices));
+                }
+                Some((sheet, full_tracks))
+            }
+            None => None,
+        };
 
         Ok(AudioFileMeta {
             audio_file: self,
             pictures,
             paddings,
             comments,
+            blobs: Vec::new(),
+            opus,
+            stream_info,
+            seek_points,
+            cue_sheet,
         })
     }
 
@@ -60,13 +118,97 @@ impl AudioFile {
         E: Executor<'a, Database = Sqlite>,
     {
         Ok(sqlx::query!(
-            "INSERT INTO files(path, name, format) VALUES(?, ?, ?);",
+            "INSERT INTO files(path, name, format, mtime, size, audio_hash) VALUES(?, ?, ?, ?, ?, ?);",
             self.path,
             self.name,
-            self.format
+            self.format,
+            self.mtime,
+            self.size,
+            self.audio_hash
         )
         .execute(pool)
         .await?
         .last_insert_rowid())
     }
+
+    /// groups every scanned file by `audio_hash`, keeping only groups with
+    /// more than one member, i.e. the same recording saved under different
+    /// tags/paths. Files that haven't been hashed yet (`audio_hash IS NULL`)
+    /// are excluded rather than lumped into one giant "group".
+    pub async fn find_duplicates<'a, E>(pool: E) -> Result<Vec<DuplicateGroup>, sqlx::Error>
+    where
+        E: Executor<'a, Database = Sqlite>,
+    {
+        let rows = sqlx::query!(
+            "SELECT audio_hash as \"audio_hash!: String\", path
+             FROM files
+             WHERE audio_hash IS NOT NULL
+               AND audio_hash IN (
+                   SELECT audio_hash FROM files
+                   WHERE audio_hash IS NOT NULL
+                   GROUP BY audio_hash
+                   HAVING COUNT(*) > 1
+               )
+             ORDER BY audio_hash"
+        )
+        .fetch_all(pool)
+        .await?;
+
+        let mut groups: Vec<DuplicateGroup> = Vec::new();
+        for row in rows {
+            match groups.last_mut() {
+                Some(group) if group.audio_hash == row.audio_hash => group.paths.push(row.path),
+                _ => groups.push(DuplicateGroup {
+                    audio_hash: row.audio_hash,
+                    paths: vec![row.path],
+                }),
+            }
+        }
+        Ok(groups)
+    }
+
+    /// true if `path` hasn't been scanned before, or its stored mtime/size
+    /// no longer match what's on disk — i.e. it needs (re)parsing.
+    pub async fn is_stale<'a, E>(
+        path: &str,
+        mtime: i64,
+        size: i64,
+        pool: E,
+    ) -> Result<bool, sqlx::Error>
+    where
+        E: Executor<'a, Database = Sqlite>,
+    {
+        let row = sqlx::query!("SELECT mtime, size FROM files WHERE path = ?", path)
+            .fetch_optional(pool)
+            .await?;
+        Ok(match row {
+            Some(row) => row.mtime != Some(mtime) || row.size != Some(size),
+            None => true,
+        })
+    }
+
+    /// deletes rows for files that are no longer present on disk, keyed by
+    /// `path`. `existing_paths` should be every path seen by the current
+    /// directory walk.
+    pub async fn prune_missing<'a, E>(
+        existing_paths: &[String],
+        pool: E,
+    ) -> Result<u64, sqlx::Error>
+    where
+        E: Executor<'a, Database = Sqlite>,
+    {
+        if existing_paths.is_empty() {
+            return sqlx::query!("DELETE FROM files")
+                .execute(pool)
+                .await
+                .map(|res| res.rows_affected());
+        }
+        let placeholders = "?,".repeat(existing_paths.len() - 1) + "?";
+        let query = format!("DELETE FROM files WHERE path NOT IN ({placeholders})");
+        let mut query = sqlx::query(&query);
+        for path in existing_paths {
+            query = query.bind(path);
+        }
+        Ok(query.execute(pool).await?.rows_affected())
+    }
 }
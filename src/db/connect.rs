@@ -0,0 +1,5 @@
+//! Connect-with-backoff logic itself lives in `musicbrainz_db_client::retry`
+//! (this crate already depends on that crate for `oni`'s search client), so
+//! this module just re-exports it rather than keeping a second copy that can
+//! drift out of sync on backoff/jitter changes.
+pub use musicbrainz_db_client::retry::{connect_with_backoff, is_transient_sqlx_error, BackoffConfig};
@@ -0,0 +1,282 @@
+use sqlx::{prelude::FromRow, Executor, Sqlite};
+
+use crate::io::reader::Corruption;
+
+const CATALOG_NUMBER_LEN: usize = 128;
+const ISRC_LEN: usize = 12;
+// catalog number (128) + lead-in (8) + is_cd/reserved (259) + track count (1)
+const CUESHEET_MIN_LEN: usize = CATALOG_NUMBER_LEN + 8 + 259 + 1;
+// track offset (8) + track number (1) + ISRC (12) + flags/reserved (14) + index count (1)
+const CUE_TRACK_MIN_LEN: usize = 8 + 1 + ISRC_LEN + 14 + 1;
+// index offset (8) + index number (1) + reserved (3)
+const CUE_INDEX_LEN: usize = 8 + 1 + 3;
+
+/// A FLAC CUESHEET block (block type `5`): the track/index layout of the CD
+/// (or CD-like source) a file was ripped from, kept around so a player or
+/// burner can reconstruct the original disc layout instead of just the
+/// single continuous stream FLAC otherwise stores.
+#[derive(Debug, Clone, FromRow)]
+pub struct CueSheet {
+    pub id: Option<i64>,
+    pub file_id: Option<i64>,
+    /// media catalog number (UPC/EAN), ASCII, NUL-padded to 128 bytes in the
+    /// file; trailing NULs are stripped here
+    pub catalog_number: String,
+    /// samples before the first index point of the first track, i.e. the
+    /// disc's lead-in
+    pub lead_in_samples: i64,
+    pub is_cd: bool,
+}
+
+/// One track of a `CueSheet`.
+#[derive(Debug, Clone, FromRow)]
+pub struct CueTrack {
+    pub id: Option<i64>,
+    pub cue_sheet_id: Option<i64>,
+    /// offset of this track's first index point, in samples from the start
+    /// of the FLAC audio stream
+    pub track_offset: i64,
+    pub track_number: i64,
+    /// ISRC, ASCII, NUL-padded to 12 bytes in the file; trailing NULs stripped
+    pub isrc: String,
+    pub audio: bool,
+    pub pre_emphasis: bool,
+}
+
+/// One index point of a `CueTrack`.
+#[derive(Debug, Clone, FromRow)]
+pub struct CueIndex {
+    pub id: Option<i64>,
+    pub cue_track_id: Option<i64>,
+    pub index_number: i64,
+    /// offset of this index point, in samples from the track's offset
+    pub offset: i64,
+}
+
+fn trim_nul_ascii(bytes: &[u8]) -> String {
+    let end = bytes.iter().position(|&b| b == 0).unwrap_or(bytes.len());
+    String::from_utf8_lossy(&bytes[..end]).to_string()
+}
+
+impl CueSheet {
+    /// Parses a CUESHEET block body (magic/length already consumed by the
+    /// caller) into the sheet itself and its tracks, each paired with its
+    /// own index points. `block_ptr` is only used to annotate a `Corruption`
+    /// if the declared track/index counts don't actually fit in `block`.
+    pub fn parse_block(
+        block: &[u8],
+        block_ptr: i64,
+    ) -> Result<(Self, Vec<(CueTrack, Vec<CueIndex>)>), Corruption> {
+        let too_short = |message: String| Corruption {
+            path: "".into(),
+            file_cursor: block_ptr as u64,
+            message,
+            cause: None,
+        };
+
+        if block.len() < CUESHEET_MIN_LEN {
+            return Err(too_short(format!(
+                "Not enough bytes for CUESHEET block. Length: {}",
+                block.len()
+            )));
+        }
+
+        let catalog_number = trim_nul_ascii(&block[0..CATALOG_NUMBER_LEN]);
+        let mut cursor = CATALOG_NUMBER_LEN;
+
+        let lead_in_samples = u64::from_be_bytes(block[cursor..cursor + 8].try_into().unwrap());
+        cursor += 8;
+
+        // 1 bit "is CD" flag + 7 reserved bits, then 258 reserved bytes
+        let is_cd = block[cursor] & 0b1000_0000 != 0;
+        cursor += 1 + 258;
+
+        let num_tracks = block[cursor] as usize;
+        cursor += 1;
+
+        let mut tracks = Vec::with_capacity(num_tracks);
+        for _ in 0..num_tracks {
+            if block.len() < cursor + CUE_TRACK_MIN_LEN {
+                return Err(too_short(format!(
+                    "CUESHEET block ended mid-track. Length: {}",
+                    block.len()
+                )));
+            }
+
+            let track_offset = u64::from_be_bytes(block[cursor..cursor + 8].try_into().unwrap());
+            cursor += 8;
+            let track_number = block[cursor] as i64;
+            cursor += 1;
+            let isrc = trim_nul_ascii(&block[cursor..cursor + ISRC_LEN]);
+            cursor += ISRC_LEN;
+
+            // 1 bit audio flag (0 = audio) + 1 bit pre-emphasis + 6 reserved
+            // bits, then 13 reserved bytes
+            let flags = block[cursor];
+            let audio = flags & 0b1000_0000 == 0;
+            let pre_emphasis = flags & 0b0100_0000 != 0;
+            cursor += 1 + 13;
+
+            let num_indices = block[cursor] as usize;
+            cursor += 1;
+
+            let mut indices = Vec::with_capacity(num_indices);
+            for _ in 0..num_indices {
+                if block.len() < cursor + CUE_INDEX_LEN {
+                    return Err(too_short(format!(
+                        "CUESHEET block ended mid-index. Length: {}",
+                        block.len()
+                    )));
+                }
+
+                let offset = u64::from_be_bytes(block[cursor..cursor + 8].try_into().unwrap());
+                cursor += 8;
+                let index_number = block[cursor] as i64;
+                cursor += 1 + 3;
+                indices.push(CueIndex {
+                    id: None,
+                    cue_track_id: None,
+                    index_number,
+                    offset: offset as i64,
+                });
+            }
+
+            tracks.push((
+                CueTrack {
+                    id: None,
+                    cue_sheet_id: None,
+                    track_offset: track_offset as i64,
+                    track_number,
+                    isrc,
+                    audio,
+                    pre_emphasis,
+                },
+                indices,
+            ));
+        }
+
+        Ok((
+            CueSheet {
+                id: None,
+                file_id: None,
+                catalog_number,
+                lead_in_samples: lead_in_samples as i64,
+                is_cd,
+            },
+            tracks,
+        ))
+    }
+
+    pub async fn insert<'a, E>(&self, file_id: i64, pool: E) -> Result<i64, sqlx::Error>
+    where
+        E: Executor<'a, Database = Sqlite>,
+    {
+        Ok(sqlx::query!(
+            "INSERT INTO cue_sheets(file_id, catalog_number, lead_in_samples, is_cd) VALUES(?, ?, ?, ?);",
+            file_id,
+            self.catalog_number,
+            self.lead_in_samples,
+            self.is_cd
+        )
+        .execute(pool)
+        .await?
+        .last_insert_rowid())
+    }
+
+    pub async fn from_file_id<'a, E>(file_id: i64, pool: E) -> Result<Option<Self>, sqlx::Error>
+    where
+        E: Executor<'a, Database = Sqlite>,
+    {
+        sqlx::query_as!(Self, "SELECT * FROM cue_sheets WHERE file_id = ?", file_id)
+            .fetch_optional(pool)
+            .await
+    }
+}
+
+impl CueTrack {
+    pub async fn insert<'a, E>(&self, cue_sheet_id: i64, pool: E) -> Result<i64, sqlx::Error>
+    where
+        E: Executor<'a, Database = Sqlite>,
+    {
+        Ok(sqlx::query!(
+            "INSERT INTO cue_tracks(cue_sheet_id, track_offset, track_number, isrc, audio, pre_emphasis)
+             VALUES(?, ?, ?, ?, ?, ?);",
+            cue_sheet_id,
+            self.track_offset,
+            self.track_number,
+            self.isrc,
+            self.audio,
+            self.pre_emphasis
+        )
+        .execute(pool)
+        .await?
+        .last_insert_rowid())
+    }
+
+    pub async fn from_cue_sheet_id<'a, E>(
+        cue_sheet_id: i64,
+        pool: E,
+    ) -> Result<Vec<Self>, sqlx::Error>
+    where
+        E: Executor<'a, Database = Sqlite>,
+    {
+        sqlx::query_as!(
+            Self,
+            "SELECT * FROM cue_tracks WHERE cue_sheet_id = ?",
+            cue_sheet_id
+        )
+        .fetch_all(pool)
+        .await
+    }
+}
+
+impl CueIndex {
+    pub async fn insert_many<'a, E>(
+        cue_track_id: i64,
+        indices: Vec<Self>,
+        pool: E,
+    ) -> Result<(), sqlx::Error>
+    where
+        E: Executor<'a, Database = Sqlite>,
+    {
+        if indices.is_empty() {
+            return Ok(());
+        }
+
+        let mut query =
+            "INSERT INTO cue_indices(cue_track_id, index_number, offset) VALUES".to_owned();
+        for i in 0..indices.len() {
+            if i > 0 {
+                query.push(',');
+            }
+            query.push_str("(?, ?, ?)");
+        }
+        query.push(';');
+
+        let mut query: sqlx::query::Query<'_, Sqlite, _> = sqlx::query(&query);
+        for index in indices {
+            query = query
+                .bind(cue_track_id)
+                .bind(index.index_number)
+                .bind(index.offset);
+        }
+        query.execute(pool).await?;
+        Ok(())
+    }
+
+    pub async fn from_cue_track_id<'a, E>(
+        cue_track_id: i64,
+        pool: E,
+    ) -> Result<Vec<Self>, sqlx::Error>
+    where
+        E: Executor<'a, Database = Sqlite>,
+    {
+        sqlx::query_as!(
+            Self,
+            "SELECT * FROM cue_indices WHERE cue_track_id = ?",
+            cue_track_id
+        )
+        .fetch_all(pool)
+        .await
+    }
+}
@@ -0,0 +1,133 @@
+use sqlx::{prelude::FromRow, Executor, Sqlite};
+
+use crate::io::reader::Corruption;
+
+// magic (8) + version (1) + channel count (1) + pre-skip (2) + sample rate (4)
+// + output gain (2) + channel mapping family (1)
+const OPUS_HEAD_MIN_LEN: usize = 19;
+// stream count (1) + coupled count (1)
+const CHANNEL_MAPPING_TABLE_MIN_LEN: usize = 2;
+
+/// The `OpusHead` identification header: everything a player needs to set
+/// up decoding (channel layout, sample rate) and loudness normalization
+/// (output gain) before the first audio packet arrives.
+#[derive(Debug, Clone, FromRow)]
+pub struct OpusMeta {
+    pub id: Option<i64>,
+    pub file_id: Option<i64>,
+    pub version: i64,
+    pub channel_count: i64,
+    pub pre_skip: i64,
+    pub input_sample_rate: i64,
+    /// Q7.8 fixed-point dB gain to apply on playback, per the Opus spec
+    pub output_gain: i64,
+    pub channel_mapping_family: i64,
+    /// only set when `channel_mapping_family != 0`
+    pub stream_count: Option<i64>,
+    /// only set when `channel_mapping_family != 0`
+    pub coupled_count: Option<i64>,
+    /// one entry per channel, only set when `channel_mapping_family != 0`
+    pub channel_mapping: Option<Vec<u8>>,
+}
+
+impl OpusMeta {
+    /// Parses an `OpusHead` packet body (magic included) per RFC 7845
+    /// section 5.1: 8-byte magic, u8 version, u8 channel count, little-
+    /// endian u16 pre-skip, little-endian u32 input sample rate, little-
+    /// endian i16 output gain, u8 channel mapping family, then — only if
+    /// that family is non-zero — a mapping table of stream count, coupled
+    /// count, and one channel-map byte per channel.
+    pub fn parse(packet: &[u8]) -> Result<Self, Corruption> {
+        let too_short = |message: String| Corruption {
+            path: "".into(),
+            file_cursor: 0,
+            message,
+            cause: None,
+        };
+
+        if packet.len() < OPUS_HEAD_MIN_LEN {
+            return Err(too_short(format!(
+                "Not enough bytes for OpusHead packet. Length: {}",
+                packet.len()
+            )));
+        }
+
+        let channel_count = packet[9] as i64;
+        let channel_mapping_family = packet[18] as i64;
+
+        let (stream_count, coupled_count, channel_mapping) = if channel_mapping_family != 0 {
+            let mapping_len = CHANNEL_MAPPING_TABLE_MIN_LEN + channel_count as usize;
+            if packet.len() < OPUS_HEAD_MIN_LEN + mapping_len {
+                return Err(too_short(format!(
+                    "Not enough bytes for OpusHead channel mapping table. Length: {}",
+                    packet.len()
+                )));
+            }
+
+            (
+                Some(packet[19] as i64),
+                Some(packet[20] as i64),
+                Some(packet[21..21 + channel_count as usize].to_vec()),
+            )
+        } else {
+            (None, None, None)
+        };
+
+        Ok(OpusMeta {
+            id: None,
+            file_id: None,
+            version: packet[8] as i64,
+            channel_count,
+            pre_skip: u16::from_le_bytes(packet[10..12].try_into().unwrap()) as i64,
+            input_sample_rate: u32::from_le_bytes(packet[12..16].try_into().unwrap()) as i64,
+            output_gain: i16::from_le_bytes(packet[16..18].try_into().unwrap()) as i64,
+            channel_mapping_family,
+            stream_count,
+            coupled_count,
+            channel_mapping,
+        })
+    }
+
+    pub async fn from_file_id<'a, E>(file_id: i64, pool: E) -> Result<Option<Self>, sqlx::Error>
+    where
+        E: Executor<'a, Database = Sqlite>,
+    {
+        sqlx::query_as!(Self, "SELECT * FROM opus_meta WHERE file_id = ?", file_id)
+            .fetch_optional(pool)
+            .await
+    }
+
+    pub async fn insert<'a, E>(&self, file_id: i64, pool: E) -> Result<i64, sqlx::Error>
+    where
+        E: Executor<'a, Database = Sqlite>,
+    {
+        Ok(sqlx::query!(
+            "INSERT INTO opus_meta(
+                file_id,
+                version,
+                channel_count,
+                pre_skip,
+                input_sample_rate,
+                output_gain,
+                channel_mapping_family,
+                stream_count,
+                coupled_count,
+                channel_mapping
+                )
+            VALUES(?, ?, ?, ?, ?, ?, ?, ?, ?, ?);",
+            file_id,
+            self.version,
+            self.channel_count,
+            self.pre_skip,
+            self.input_sample_rate,
+            self.output_gain,
+            self.channel_mapping_family,
+            self.stream_count,
+            self.coupled_count,
+            self.channel_mapping
+        )
+        .execute(pool)
+        .await?
+        .last_insert_rowid())
+    }
+}
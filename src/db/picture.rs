@@ -1,5 +1,14 @@
+use base64::{engine::general_purpose, Engine as _};
 use sqlx::{prelude::FromRow, Executor, Sqlite};
 
+use crate::{
+    formats::opus_ogg::VORBIS_PICTURE_MARKER,
+    io::{
+        ogg::OggPageReader,
+        reader::{Corruption, UringBufReader},
+    },
+};
+
 #[derive(Debug, Clone, FromRow)]
 pub struct Picture {
     pub id: Option<i64>,
@@ -13,6 +22,93 @@ pub struct Picture {
     pub color_depth: i64,
     pub indexed_color_number: i64,
     pub size: i64,
+    /// true if sourced from a base64 `metadata_block_picture` Vorbis comment
+    /// (Ogg/Opus) rather than a native FLAC PICTURE block
+    pub vorbis_comment: bool,
+    /// content hash of the decoded image bytes, referencing a row in
+    /// `picture_blobs` (see `VorbisBlob`); `None` if the bytes weren't
+    /// extracted for this picture
+    pub blob_hash: Option<String>,
+    /// the Ogg page header immediately before this comment, needed to
+    /// re-sync an `OggPageReader` onto `file_ptr` later (see `read_data_ogg`);
+    /// `None` for native FLAC PICTURE blocks
+    pub last_ogg_header_ptr: Option<i64>,
+}
+
+/// A content-addressed binary blob, deduplicated by hash.
+///
+/// Used to store embedded cover art once per distinct image instead of once
+/// per track, since an album's tracks typically embed the same picture.
+#[derive(Debug, Clone, FromRow)]
+pub struct VorbisBlob {
+    pub hash: String,
+    pub data: Vec<u8>,
+}
+
+impl VorbisBlob {
+    /// hashes `data` with blake3 and builds a blob ready to be deduped/inserted
+    pub fn new(data: Vec<u8>) -> Self {
+        let hash = blake3::hash(&data).to_hex().to_string();
+        Self { hash, data }
+    }
+
+    pub async fn hash_exists<'a, E>(hash: String, pool: E) -> Result<bool, sqlx::Error>
+    where
+        E: Executor<'a, Database = Sqlite>,
+    {
+        let exists = sqlx::query_scalar!(
+            "SELECT EXISTS(SELECT 1 FROM picture_blobs WHERE hash = ?)",
+            hash
+        )
+        .fetch_one(pool)
+        .await?;
+        Ok(exists != 0)
+    }
+
+    pub async fn insert<'a, E>(&self, pool: E) -> Result<(), sqlx::Error>
+    where
+        E: Executor<'a, Database = Sqlite>,
+    {
+        sqlx::query!(
+            "INSERT INTO picture_blobs(hash, data) VALUES(?, ?)",
+            self.hash,
+            self.data
+        )
+        .execute(pool)
+        .await?;
+        Ok(())
+    }
+
+    /// fetches the stored image bytes for a picture's `blob_hash`, e.g. for
+    /// cover-art export tooling
+    pub async fn from_hash<'a, E>(hash: &str, pool: E) -> Result<Option<Self>, sqlx::Error>
+    where
+        E: Executor<'a, Database = Sqlite>,
+    {
+        sqlx::query_as!(Self, "SELECT * FROM picture_blobs WHERE hash = ?", hash)
+            .fetch_optional(pool)
+            .await
+    }
+}
+
+/// Sniffs `data`'s magic bytes and, if they identify a known image format
+/// that doesn't match `mime`, returns what the data actually looks like.
+/// Returns `None` when the magic bytes are unrecognized or agree with
+/// `mime`, since a declared type this function doesn't know about isn't
+/// grounds to flag anything.
+pub(crate) fn check_mime_magic(mime: &str, data: &[u8]) -> Option<&'static str> {
+    const JPEG_MAGIC: [u8; 3] = [0xFF, 0xD8, 0xFF];
+    const PNG_MAGIC: [u8; 4] = [0x89, 0x50, 0x4E, 0x47];
+
+    let sniffed = if data.starts_with(&JPEG_MAGIC) {
+        "image/jpeg"
+    } else if data.starts_with(&PNG_MAGIC) {
+        "image/png"
+    } else {
+        return None;
+    };
+
+    (mime != sniffed).then_some(sniffed)
 }
 
 impl Picture {
@@ -29,37 +125,87 @@ impl Picture {
         .await
     }
 
-    pub fn from_picture_block(picture: &[u8], file_ptr: i64) -> Self {
-        let mut cursor = 0;
+    /// parses a FLAC/Vorbis PICTURE block body, deduplicating the embedded
+    /// image bytes into a `VorbisBlob`. Returns `None` for the blob when the
+    /// declared `picture_len` doesn't fit in `picture` (the caller only had
+    /// the metadata prefix, e.g. an Opus comment header split across pages).
+    /// Cross-checks the declared `mime` against the image's magic bytes,
+    /// printing a diagnostic (but not failing the parse) on a mismatch —
+    /// see `check_mime_magic`.
+    pub fn from_picture_block_with_data(
+        picture: &[u8],
+        file_ptr: i64,
+        vorbis_comment: bool,
+        last_ogg_header_ptr: Option<i64>,
+    ) -> Result<(Self, Option<VorbisBlob>), Corruption> {
+        let mut result =
+            Self::from_picture_block(picture, file_ptr, vorbis_comment, last_ogg_header_ptr)?;
+        let blob = picture
+            .len()
+            .checked_sub(result.size as usize)
+            .and_then(|data_start| picture.get(data_start..))
+            .filter(|data| data.len() as i64 == result.size)
+            .map(|data| VorbisBlob::new(data.to_vec()));
+        if let Some(blob) = &blob {
+            if let Some(mismatch) = check_mime_magic(&result.mime, &blob.data) {
+                println!(
+                    "picture at offset {file_ptr} declares mime {:?} but its data looks like {mismatch}",
+                    result.mime
+                );
+            }
+        }
+        result.blob_hash = blob.as_ref().map(|b| b.hash.clone());
+        Ok((result, blob))
+    }
+
+    /// Parses a FLAC/Vorbis PICTURE block body's metadata prefix (type,
+    /// mime, description, dimensions, declared image size), bounds-checking
+    /// every variable-length field against `picture` instead of indexing it
+    /// blindly, since `picture` may be attacker-controlled or a truncated
+    /// in-memory slice.
+    pub fn from_picture_block(
+        picture: &[u8],
+        file_ptr: i64,
+        vorbis_comment: bool,
+        last_ogg_header_ptr: Option<i64>,
+    ) -> Result<Self, Corruption> {
+        let too_short = || Corruption {
+            path: "".into(),
+            file_cursor: file_ptr as u64,
+            message: format!("Not enough bytes for PICTURE block. Length: {}", picture.len()),
+            cause: None,
+        };
+        let get = |range: std::ops::Range<usize>| picture.get(range).ok_or_else(too_short);
         let get_u32 =
             |bytes: &[u8]| -> i64 { u32::from_be_bytes(bytes.try_into().unwrap()) as i64 };
 
-        let picture_type = get_u32(&picture[cursor..cursor + 4]);
+        let mut cursor = 0;
+
+        let picture_type = get_u32(get(cursor..cursor + 4)?);
         cursor += 4;
 
-        let mime_len = get_u32(&picture[cursor..cursor + 4]) as usize;
+        let mime_len = get_u32(get(cursor..cursor + 4)?) as usize;
         cursor += 4;
-        let mime_bytes = &picture[cursor..mime_len + cursor];
-        let mime = String::from_utf8_lossy(mime_bytes).to_string();
+        let mime = String::from_utf8_lossy(get(cursor..mime_len + cursor)?).to_string();
         cursor += mime_len;
 
-        let description_len = get_u32(&picture[cursor..cursor + 4]) as usize;
+        let description_len = get_u32(get(cursor..cursor + 4)?) as usize;
         cursor += 4;
-        let description_bytes = &picture[cursor..description_len + cursor];
-        let description = String::from_utf8_lossy(description_bytes).to_string();
+        let description =
+            String::from_utf8_lossy(get(cursor..description_len + cursor)?).to_string();
         cursor += description_len;
 
-        let width = get_u32(&picture[cursor..cursor + 4]);
+        let width = get_u32(get(cursor..cursor + 4)?);
         cursor += 4;
-        let height = get_u32(&picture[cursor..cursor + 4]);
+        let height = get_u32(get(cursor..cursor + 4)?);
         cursor += 4;
-        let color_depth = get_u32(&picture[cursor..cursor + 4]);
+        let color_depth = get_u32(get(cursor..cursor + 4)?);
         cursor += 4;
-        let indexed_color_number = get_u32(&picture[cursor..cursor + 4]);
+        let indexed_color_number = get_u32(get(cursor..cursor + 4)?);
         cursor += 4;
-        let picture_len = get_u32(&picture[cursor..cursor + 4]);
+        let picture_len = get_u32(get(cursor..cursor + 4)?);
 
-        Picture {
+        Ok(Picture {
             id: None,
             file_id: None,
             file_ptr,
@@ -71,7 +217,68 @@ impl Picture {
             height,
             color_depth,
             indexed_color_number,
-        }
+            vorbis_comment,
+            blob_hash: None,
+            last_ogg_header_ptr,
+        })
+    }
+
+    /// Re-reads this picture's raw image bytes from a native FLAC PICTURE
+    /// block on disk, for export/dump tooling that doesn't want a DB round
+    /// trip. Seeks to `file_ptr` and skips past the type/mime/description/
+    /// dimensions prefix already parsed in `from_picture_block`.
+    pub async fn read_data_flac(&self, reader: &mut UringBufReader) -> Result<Vec<u8>, Corruption> {
+        reader.read_at_offset(8196, self.file_ptr as u64).await?;
+        reader.skip(4).await?; // picture type
+        let mime_len = reader.read_u32().await? as u64;
+        reader.skip(mime_len).await?;
+        let description_len = reader.read_u32().await? as u64;
+        reader.skip(description_len).await?;
+        reader.skip(16).await?; // width, height, color depth, indexed color number
+        let picture_len = reader.read_u32().await? as usize;
+        Ok(reader.get_bytes(picture_len).await?.to_vec())
+    }
+
+    /// Re-reads this picture's raw image bytes from a base64
+    /// `metadata_block_picture` Vorbis comment, for export/dump tooling that
+    /// doesn't want a DB round trip. Re-syncs `ogg_reader` onto `file_ptr`
+    /// from `last_ogg_header_ptr` the same way `VorbisComment::into_bytes_ogg`
+    /// does, then decodes the base64 payload, using the same "glowing"
+    /// technique `parse_opus_vorbis` uses to read across page boundaries
+    /// without needing to know where they fall ahead of time.
+    pub async fn read_data_ogg<'a>(
+        &self,
+        ogg_reader: &mut OggPageReader<'a>,
+    ) -> anyhow::Result<Vec<u8>> {
+        let header_ptr = self
+            .last_ogg_header_ptr
+            .ok_or_else(|| anyhow::anyhow!("picture has no stored Ogg page header pointer"))?
+            as u64;
+
+        ogg_reader.reader.read_at_offset(8196, header_ptr).await?;
+        ogg_reader.cursor = ogg_reader.segment_size;
+        ogg_reader.parse_header().await?;
+        ogg_reader
+            .safe_skip(
+                (self.file_ptr as u64 - ogg_reader.reader.file_ptr - ogg_reader.reader.cursor)
+                    as usize,
+            )
+            .await?;
+        // skip the comment's [u32 length]["metadata_block_picture="] prefix
+        ogg_reader
+            .safe_skip(4 + VORBIS_PICTURE_MARKER.len() + 1)
+            .await?;
+
+        let to_base64_bytes = |bytes: usize| -> usize {
+            let base64_chars = bytes / 3 * 4;
+            let padding_chars = if bytes % 3 > 0 { 4 } else { 0 };
+            base64_chars + padding_chars
+        };
+        let prefix_len = 32 + self.mime.len() + self.description.len();
+        let encoded_len = to_base64_bytes(prefix_len + self.size as usize);
+        let decoded = general_purpose::STANDARD.decode(ogg_reader.get_bytes(encoded_len).await?)?;
+        let data_start = decoded.len() - self.size as usize;
+        Ok(decoded[data_start..].to_vec())
     }
 
     pub async fn insert<'a, E>(&self, file_id: i64, pool: E) -> Result<i64, sqlx::Error>
@@ -89,8 +296,11 @@ impl Picture {
                 height,
                 color_depth,
                 indexed_color_number,
-                size)
-            VALUES(?, ?, ?, ?, ?, ?, ?, ?, ?, ?);",
+                size,
+                vorbis_comment,
+                blob_hash,
+                last_ogg_header_ptr)
+            VALUES(?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?);",
             file_id,
             self.file_ptr,
             self.picture_type,
@@ -100,7 +310,10 @@ impl Picture {
             self.height,
             self.color_depth,
             self.indexed_color_number,
-            self.size
+            self.size,
+            self.vorbis_comment,
+            self.blob_hash,
+            self.last_ogg_header_ptr
         )
         .execute(pool)
         .await?
@@ -0,0 +1,95 @@
+use sqlx::{prelude::FromRow, Executor, Sqlite};
+
+const SEEK_POINT_SIZE: usize = 18;
+const PLACEHOLDER_SAMPLE_NUMBER: u64 = 0xFFFF_FFFF_FFFF_FFFF;
+
+/// One seek point from a FLAC SEEKTABLE block (block type `3`): lets a
+/// player jump close to `sample_number` without scanning the whole file to
+/// find it, at the cost of a little index space per point.
+#[derive(Debug, Clone, FromRow)]
+pub struct SeekPoint {
+    pub id: Option<i64>,
+    pub file_id: Option<i64>,
+    pub sample_number: i64,
+    pub byte_offset: i64,
+    pub frame_samples: i64,
+    /// true when `sample_number` is the placeholder value
+    /// `0xFFFF_FFFF_FFFF_FFFF`, reserved by the spec for encoders that want
+    /// to preallocate seek point slots without committing to real offsets
+    pub placeholder: bool,
+}
+
+impl SeekPoint {
+    /// Parses a SEEKTABLE block body (magic/length already consumed by the
+    /// caller) into its fixed-size 18-byte seek points: sample number (u64
+    /// BE), byte offset from the first frame (u64 BE), and the target
+    /// frame's sample count (u16 BE).
+    pub fn parse_block(block: &[u8]) -> Vec<Self> {
+        block
+            .chunks_exact(SEEK_POINT_SIZE)
+            .map(|entry| {
+                let sample_number = u64::from_be_bytes(entry[0..8].try_into().unwrap());
+                let byte_offset = u64::from_be_bytes(entry[8..16].try_into().unwrap());
+                let frame_samples = u16::from_be_bytes(entry[16..18].try_into().unwrap());
+                SeekPoint {
+                    id: None,
+                    file_id: None,
+                    sample_number: sample_number as i64,
+                    byte_offset: byte_offset as i64,
+                    frame_samples: frame_samples as i64,
+                    placeholder: sample_number == PLACEHOLDER_SAMPLE_NUMBER,
+                }
+            })
+            .collect()
+    }
+
+    pub async fn from_file_id<'a, E>(file_id: i64, pool: E) -> Result<Vec<Self>, sqlx::Error>
+    where
+        E: Executor<'a, Database = Sqlite>,
+    {
+        sqlx::query_as!(Self, "SELECT * FROM seek_points WHERE file_id = ?", file_id)
+            .fetch_all(pool)
+            .await
+    }
+
+    pub async fn insert_many<'a, E>(
+        file_id: i64,
+        points: Vec<Self>,
+        pool: E,
+    ) -> Result<(), sqlx::Error>
+    where
+        E: Executor<'a, Database = Sqlite>,
+    {
+        if points.is_empty() {
+            return Ok(());
+        }
+
+        let mut query = "INSERT INTO seek_points(
+                file_id,
+                sample_number,
+                byte_offset,
+                frame_samples,
+                placeholder) VALUES"
+            .to_owned();
+
+        for i in 0..points.len() {
+            if i > 0 {
+                query.push(',');
+            }
+            query.push_str("(?, ?, ?, ?, ?)");
+        }
+        query.push(';');
+
+        let mut query: sqlx::query::Query<'_, Sqlite, _> = sqlx::query(&query);
+        for point in points {
+            query = query
+                .bind(file_id)
+                .bind(point.sample_number)
+                .bind(point.byte_offset)
+                .bind(point.frame_samples)
+                .bind(point.placeholder);
+        }
+        query.execute(pool).await?;
+        Ok(())
+    }
+}
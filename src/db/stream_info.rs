@@ -0,0 +1,107 @@
+use sqlx::{prelude::FromRow, Executor, Sqlite};
+
+/// A FLAC `STREAMINFO` block (block type `0`): the only place a FLAC file
+/// records its sample rate, channel count, bit depth, duration, and the MD5
+/// of the unencoded audio. Every FLAC stream opens with exactly one of
+/// these.
+#[derive(Debug, Clone, FromRow)]
+pub struct StreamInfo {
+    pub id: Option<i64>,
+    pub file_id: Option<i64>,
+    pub min_block_size: i64,
+    pub max_block_size: i64,
+    pub min_frame_size: i64,
+    pub max_frame_size: i64,
+    pub sample_rate: i64,
+    pub channels: i64,
+    pub bits_per_sample: i64,
+    pub total_samples: i64,
+    /// `total_samples / sample_rate`; `None` when `sample_rate` is 0 (a
+    /// malformed STREAMINFO, but not worth failing the whole parse over)
+    pub duration_seconds: Option<f64>,
+    /// 128-bit MD5 of the unencoded audio, 16 bytes
+    pub md5: Vec<u8>,
+}
+
+impl StreamInfo {
+    /// Parses a STREAMINFO block body (34 bytes, magic/length already
+    /// consumed by the caller). The block and frame sizes are whole bytes;
+    /// sample rate, channels, bits per sample and total samples are
+    /// bit-packed MSB-first across bytes 10..18, so those 8 bytes are read
+    /// as one big-endian `u64` and sliced with shifts/masks.
+    pub fn parse(block: &[u8]) -> Self {
+        let min_block_size = u16::from_be_bytes(block[0..2].try_into().unwrap()) as i64;
+        let max_block_size = u16::from_be_bytes(block[2..4].try_into().unwrap()) as i64;
+        let min_frame_size = u32::from_be_bytes([0, block[4], block[5], block[6]]) as i64;
+        let max_frame_size = u32::from_be_bytes([0, block[7], block[8], block[9]]) as i64;
+
+        // sample_rate(20) | channels-1(3) | bits_per_sample-1(5) | total_samples(36)
+        let packed = u64::from_be_bytes(block[10..18].try_into().unwrap());
+        let sample_rate = (packed >> 44) as i64;
+        let channels = ((packed >> 41) & 0b111) as i64 + 1;
+        let bits_per_sample = ((packed >> 36) & 0b1_1111) as i64 + 1;
+        let total_samples = (packed & 0xF_FFFF_FFFF) as i64;
+
+        let duration_seconds = (sample_rate > 0).then(|| total_samples as f64 / sample_rate as f64);
+
+        StreamInfo {
+            id: None,
+            file_id: None,
+            min_block_size,
+            max_block_size,
+            min_frame_size,
+            max_frame_size,
+            sample_rate,
+            channels,
+            bits_per_sample,
+            total_samples,
+            duration_seconds,
+            md5: block[18..34].to_vec(),
+        }
+    }
+
+    pub async fn from_file_id<'a, E>(file_id: i64, pool: E) -> Result<Option<Self>, sqlx::Error>
+    where
+        E: Executor<'a, Database = Sqlite>,
+    {
+        sqlx::query_as!(Self, "SELECT * FROM stream_info WHERE file_id = ?", file_id)
+            .fetch_optional(pool)
+            .await
+    }
+
+    pub async fn insert<'a, E>(&self, file_id: i64, pool: E) -> Result<i64, sqlx::Error>
+    where
+        E: Executor<'a, Database = Sqlite>,
+    {
+        Ok(sqlx::query!(
+            "INSERT INTO stream_info(
+                file_id,
+                min_block_size,
+                max_block_size,
+                min_frame_size,
+                max_frame_size,
+                sample_rate,
+                channels,
+                bits_per_sample,
+                total_samples,
+                duration_seconds,
+                md5
+                )
+            VALUES(?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?);",
+            file_id,
+            self.min_block_size,
+            self.max_block_size,
+            self.min_frame_size,
+            self.max_frame_size,
+            self.sample_rate,
+            self.channels,
+            self.bits_per_sample,
+            self.total_samples,
+            self.duration_seconds,
+            self.md5
+        )
+        .execute(pool)
+        .await?
+        .last_insert_rowid())
+    }
+}
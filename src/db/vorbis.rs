@@ -143,15 +143,43 @@ impl VorbisComment {
         Ok(())
     }
     /// vorbis comment string into key,val pair
+    /// the key is lowercased for case-insensitive lookups (per the Vorbis spec,
+    /// field names are case-insensitive), but the value keeps its original
+    /// case so round-tripping a tag like `ARTIST=Radiohead` doesn't mangle it
     pub fn into_key_val(comment: &[u8]) -> Option<(String, String)> {
         comment.iter().position(|&b| b == b'=').map(|index| {
             (
                 String::from_utf8_lossy(&comment[..index]).to_lowercase(),
-                String::from_utf8_lossy(&comment[index + 1..]).to_lowercase(),
+                String::from_utf8_lossy(&comment[index + 1..]).to_string(),
             )
         })
     }
 
+    /// Serializes a vendor string and ordered key=value comments into a
+    /// VORBIS_COMMENT block body, the inverse of `parse_block`.
+    pub fn serialize_block(vendor: &str, comments: &[(String, String)]) -> Vec<u8> {
+        let mut body = Vec::new();
+        body.extend((vendor.len() as u32).to_le_bytes());
+        body.extend(vendor.as_bytes());
+        body.extend((comments.len() as u32).to_le_bytes());
+        for (key, value) in comments {
+            body.extend(Self::serialize_comment(key, value));
+        }
+        body
+    }
+
+    /// Serializes a single comment as `[u32 length][key=value]`, the unit
+    /// both FLAC's VORBIS_COMMENT block and Ogg's comment header lay their
+    /// comment list out of.
+    pub fn serialize_comment(key: &str, value: &str) -> Vec<u8> {
+        let mut comment = Vec::with_capacity(key.len() + value.len() + 5);
+        comment.extend((key.len() as u32 + value.len() as u32 + 1).to_le_bytes());
+        comment.extend(key.as_bytes());
+        comment.push(b'=');
+        comment.extend(value.as_bytes());
+        comment
+    }
+
     pub async fn parse_block(
         vorbis_block: Vec<u8>,
         block_ptr: i64,
@@ -164,6 +192,7 @@ impl VorbisComment {
                 path: "".into(),
                 file_cursor: block_ptr as u64,
                 message: "Corrupted VorbisBlock".to_owned(),
+                cause: None,
             })?) as usize;
         let vendor = String::from_utf8_lossy(&vorbis_block[4..vendor_len + 4]).to_string();
         let mut comment_cursor = vendor_len + 4;
@@ -175,6 +204,7 @@ impl VorbisComment {
                     path: "".into(),
                     file_cursor: block_ptr as u64,
                     message: "Corrupted VorbisBlock".to_owned(),
+                    cause: None,
                 })?,
         ) as usize;
         let mut comment_len = u32::from_le_bytes(
@@ -184,6 +214,7 @@ impl VorbisComment {
                     path: "".into(),
                     file_cursor: block_ptr as u64,
                     message: "Corrupted VorbisBlock".to_owned(),
+                    cause: None,
                 })?,
         ) as usize;
 
@@ -222,6 +253,7 @@ impl VorbisComment {
                         path: "".into(),
                         file_cursor: block_ptr as u64,
                         message: "Corrupted VorbisBlock".to_owned(),
+                        cause: None,
                     })?,
             ) as usize;
         }
@@ -231,6 +263,7 @@ impl VorbisComment {
                 file_cursor: block_ptr as u64,
                 path: "".into(),
                 message: "Comment amount does not match vorbis comment list length".to_owned(),
+                cause: None,
             });
         }
         let vorbis_meta = VorbisMeta {
@@ -0,0 +1,187 @@
+use std::ops::Range;
+
+use blake3::Hasher;
+
+use crate::{
+    db::audio_file::AudioFileMeta,
+    io::reader::{Corruption, UringBufReader},
+};
+
+/// Byte ranges that are tag/container overhead rather than audio payload,
+/// already located by the parsers that built `meta`: vorbis comment blocks
+/// (native FLAC, Ogg, and the ID3v2 span `parse_id3` reuses the same
+/// `VorbisMeta` shape for), native FLAC PICTURE blocks, and PADDING runs.
+/// PADDING is included even though the request only calls out comment/ID3/
+/// picture spans, because `write_vorbis_comment` reuses and resizes the
+/// PADDING block that follows a rewritten comment — without excluding it
+/// too, retagging a file would shift the hash just by growing or shrinking
+/// its padding.
+fn excluded_ranges(meta: &AudioFileMeta) -> Vec<Range<u64>> {
+    let mut ranges = Vec::new();
+
+    // native FLAC comment/picture/padding pointers sit just past each block's
+    // 4-byte header, whose 24-bit length changes when the block is resized,
+    // so that header is excluded along with the body
+    let block_header_len: u64 = if meta.audio_file.format.as_deref() == Some("flac") {
+        4
+    } else {
+        0
+    };
+
+    for (vorbis_meta, _) in &meta.comments {
+        ranges.push(vorbis_meta.file_ptr as u64 - block_header_len..vorbis_meta.end_ptr as u64);
+    }
+
+    for picture in &meta.pictures {
+        if picture.vorbis_comment {
+            // lives inside a `metadata_block_picture` comment value, already
+            // covered by that comment's VorbisMeta range above
+            continue;
+        }
+        // type(4) + mime_len(4) + mime + desc_len(4) + desc + width(4) +
+        // height(4) + color_depth(4) + indexed_color_number(4) + picture_len(4)
+        let prefix = 32 + picture.mime.len() as u64 + picture.description.len() as u64;
+        let block_len = prefix + picture.size as u64;
+        ranges.push(
+            picture.file_ptr as u64 - block_header_len..picture.file_ptr as u64 + block_len,
+        );
+    }
+
+    for padding in &meta.paddings {
+        if let (Some(file_ptr), Some(byte_size)) = (padding.file_ptr, padding.byte_size) {
+            ranges.push(file_ptr as u64 - block_header_len..file_ptr as u64 + byte_size as u64);
+        }
+    }
+
+    ranges.sort_by_key(|range| range.start);
+    ranges
+}
+
+/// Every sub-range of `0..file_size` not covered by `excluded`, which must
+/// already be sorted by `start` (ranges may still overlap or touch).
+fn complement(excluded: &[Range<u64>], file_size: u64) -> Vec<Range<u64>> {
+    let mut gaps = Vec::new();
+    let mut cursor = 0u64;
+    for range in excluded {
+        if range.start > cursor {
+            gaps.push(cursor..range.start);
+        }
+        cursor = cursor.max(range.end);
+    }
+    if cursor < file_size {
+        gaps.push(cursor..file_size);
+    }
+    gaps
+}
+
+/// Hashes everything in the file except tag/container metadata, so two
+/// native FLAC copies of the same recording saved with different tags land
+/// on the same digest, and retagging a native FLAC file never changes its
+/// own digest. Ogg page headers (sequence numbers, CRCs) are not excluded,
+/// so for an Ogg stream a comment rewrite, which re-checksums the comment
+/// pages, changes the digest. Reads
+/// through `reader`'s range cache (`UringBufReader::get_range`) rather than
+/// its sequential buffer, since the gaps to hash usually aren't contiguous.
+pub async fn hash_audio_payload(
+    reader: &UringBufReader,
+    meta: &AudioFileMeta,
+    file_size: u64,
+) -> Result<String, Corruption> {
+    let excluded = excluded_ranges(meta);
+    let mut hasher = Hasher::new();
+    for range in complement(&excluded, file_size) {
+        let bytes = reader.get_range(range).await?;
+        hasher.update(&bytes);
+    }
+    Ok(hasher.finalize().to_hex().to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        db::vorbis::{FLAC_MARKER, VorbisComment},
+        formats::flac::{parse_flac, write_vorbis_comment},
+        io::backend::{FileBackend, FileHandle},
+    };
+    use std::fs;
+
+    /// Builds a minimal single-STREAMINFO, single-VORBIS_COMMENT FLAC file
+    /// (no PADDING block, so any comment rewrite has to go through
+    /// `rewrite_flac_comment`'s copy-and-truncate path) followed by `audio`
+    /// as its payload.
+    fn build_flac(vendor: &str, comments: &[(String, String)], audio: &[u8]) -> Vec<u8> {
+        let mut bytes = FLAC_MARKER.to_vec();
+
+        bytes.push(0); // STREAMINFO, not last block
+        bytes.extend(&34u32.to_be_bytes()[1..4]);
+        bytes.extend([0u8; 34]);
+
+        let body = VorbisComment::serialize_block(vendor, comments);
+        bytes.push(0b1000_0100); // VORBIS_COMMENT, last block
+        bytes.extend(&(body.len() as u32).to_be_bytes()[1..4]);
+        bytes.extend(body);
+
+        bytes.extend_from_slice(audio);
+        bytes
+    }
+
+    /// Re-opens `path` from scratch and parses it, mirroring the cursor
+    /// bookkeeping `read_with_uring` does (read a chunk, sniff the marker,
+    /// hand the positioned reader to the format parser).
+    async fn read_meta(path: std::path::PathBuf) -> (UringBufReader, AudioFileMeta, u64) {
+        let size = fs::metadata(&path).unwrap().len();
+        let file = <FileHandle as FileBackend>::open(&path).await.unwrap();
+        let mut reader = UringBufReader::new(file, path).with_file_size(size);
+        reader.read_next(8196).await.unwrap();
+        let marker: [u8; 4] = reader.get_bytes(4).await.unwrap().try_into().unwrap();
+        assert_eq!(marker, FLAC_MARKER);
+        let meta = parse_flac(&mut reader).await.unwrap();
+        (reader, meta, size)
+    }
+
+    /// The explicit invariant `excluded_ranges`/`hash_audio_payload` exist to
+    /// uphold: retagging a file (here, rewriting its VORBIS_COMMENT block)
+    /// must never change the resulting audio hash.
+    #[test]
+    fn hash_is_stable_across_comment_rewrite() {
+        tokio_uring::start(async {
+            let path = std::env::temp_dir()
+                .join(format!("music_maid_hash_stability_{}.flac", std::process::id()));
+            let audio: Vec<u8> = (0u8..64).map(|b| b ^ 0xAB).collect();
+            let original = build_flac(
+                "testvendor",
+                &[("artist".to_owned(), "Test Artist".to_owned())],
+                &audio,
+            );
+            fs::write(&path, &original).unwrap();
+
+            let (mut reader, meta, size) = read_meta(path.clone()).await;
+            let hash_before = hash_audio_payload(&reader, &meta, size).await.unwrap();
+
+            let (vorbis_meta, _) = &meta.comments[0];
+            write_vorbis_comment(
+                &mut reader,
+                vorbis_meta,
+                None,
+                "testvendor",
+                &[(
+                    "artist".to_owned(),
+                    "A Completely Different Artist Name".to_owned(),
+                )],
+            )
+            .await
+            .unwrap();
+
+            let (reader, meta, size) = read_meta(path.clone()).await;
+            let hash_after = hash_audio_payload(&reader, &meta, size).await.unwrap();
+
+            fs::remove_file(&path).unwrap();
+
+            assert_eq!(
+                hash_before, hash_after,
+                "rewriting a comment block must not change the audio hash"
+            );
+        });
+    }
+}
@@ -0,0 +1,49 @@
+use crate::{
+    db::audio_file::AudioFileMeta,
+    io::reader::{Corruption, UringBufReader},
+};
+
+use super::{flac::parse_flac, id3::parse_id3, opus_ogg::parse_ogg_pages};
+
+/// Common entry point every tag format implements, so `read_with_uring` can
+/// dispatch on the sniffed leading magic bytes without the rest of the
+/// pipeline (queueing, SQLite insertion) caring which container it read.
+/// `marker` is the same 4 bytes the caller sniffed to pick the impl in the
+/// first place, handed back in case a format needs more of it than just the
+/// magic (ID3v2's major version lives in its 4th byte).
+pub trait TagContainer {
+    async fn parse(
+        reader: &mut UringBufReader,
+        marker: [u8; 4],
+    ) -> Result<AudioFileMeta, Corruption>;
+}
+
+pub struct Flac;
+impl TagContainer for Flac {
+    async fn parse(
+        reader: &mut UringBufReader,
+        _marker: [u8; 4],
+    ) -> Result<AudioFileMeta, Corruption> {
+        parse_flac(reader).await
+    }
+}
+
+pub struct OggOpus;
+impl TagContainer for OggOpus {
+    async fn parse(
+        reader: &mut UringBufReader,
+        _marker: [u8; 4],
+    ) -> Result<AudioFileMeta, Corruption> {
+        parse_ogg_pages(reader).await
+    }
+}
+
+pub struct Id3v2;
+impl TagContainer for Id3v2 {
+    async fn parse(
+        reader: &mut UringBufReader,
+        marker: [u8; 4],
+    ) -> Result<AudioFileMeta, Corruption> {
+        parse_id3(reader, marker[3]).await
+    }
+}
@@ -1,12 +1,44 @@
 use crate::{
     db::{
         audio_file::{AudioFile, AudioFileMeta},
+        cue_sheet::{CueIndex, CueSheet, CueTrack},
         padding::Padding,
-        picture::Picture,
-        vorbis::VorbisComment,
+        picture::{check_mime_magic, Picture, VorbisBlob},
+        seek_table::SeekPoint,
+        stream_info::StreamInfo,
+        vorbis::{VorbisComment, VorbisMeta},
+    },
+    io::{
+        ogg::OggPageReader,
+        reader::{Corruption, UringBufReader},
     },
-    io::reader::{Corruption, UringBufReader},
 };
+use async_stream::try_stream;
+use futures::{Stream, StreamExt};
+use std::os::fd::AsRawFd;
+
+/// Mapping header packet type byte (`0x7F`) + "FLAC", identifying an
+/// OggFLAC logical bitstream's first packet (Ogg FLAC mapping, draft
+/// section 4), as distinct from native FLAC's leading `fLaC` marker.
+pub const OGG_FLAC_MARKER: [u8; 5] = [0x7F, 0x46, 0x4C, 0x41, 0x43];
+/// offset of the mapping header's embedded STREAMINFO block within the
+/// first packet: `0x7F "FLAC"` (5) + major/minor version (2) + header
+/// packet count (2) + native `fLaC` signature (4)
+const OGG_FLAC_HEADER_PREFIX_LEN: usize = 13;
+
+/// chunk size used to copy the audio data/trailing blocks forward when
+/// `rewrite_flac_comment` has to shift them to make room for a bigger
+/// VORBIS_COMMENT block
+const REWRITE_COPY_CHUNK: usize = 8196;
+
+const LAST_BLOCK_FLAG: u8 = 0b1000_0000;
+
+#[allow(non_camel_case_types)]
+struct STREAMINFO_MARKER;
+impl STREAMINFO_MARKER {
+    const END_OF_BLOCK: u8 = 0b10000000;
+    const MARKER: u8 = 0b00000000;
+}
 
 #[allow(non_camel_case_types)]
 struct VORBIS_COMMENT_MARKER;
@@ -29,6 +61,38 @@ impl PADDING_MARKER {
     const MARKER: u8 = 0b00000001;
 }
 
+#[allow(non_camel_case_types)]
+struct SEEKTABLE_MARKER;
+impl SEEKTABLE_MARKER {
+    const END_OF_BLOCK: u8 = 0b10000011;
+    const MARKER: u8 = 0b00000011;
+}
+
+#[allow(non_camel_case_types)]
+struct CUESHEET_MARKER;
+impl CUESHEET_MARKER {
+    const END_OF_BLOCK: u8 = 0b10000101;
+    const MARKER: u8 = 0b00000101;
+}
+
+/// One parsed FLAC metadata block, yielded incrementally by
+/// `parse_flac_stream` so a caller can stop reading once it has the blocks
+/// it wants. A PICTURE block the stream reaches is always read in full,
+/// image bytes included, before it is yielded.
+/// `Unknown` carries the marker byte and declared length of a block type
+/// this crate doesn't otherwise recognize, which `parse_flac_stream` has
+/// already skipped past by the time it's yielded.
+#[derive(Debug, Clone)]
+pub enum MetadataBlock {
+    StreamInfo(StreamInfo),
+    VorbisComment(VorbisMeta, Vec<VorbisComment>),
+    Picture(Picture, Option<VorbisBlob>),
+    Padding(Padding),
+    SeekTable(Vec<SeekPoint>),
+    CueSheet(CueSheet, Vec<(CueTrack, Vec<CueIndex>)>),
+    Unknown { marker: u8, length: usize },
+}
+
 pub async fn parse_flac(reader: &mut UringBufReader) -> Result<AudioFileMeta, Corruption> {
     let audio_file = AudioFile {
         id: None,
@@ -40,105 +104,213 @@ pub async fn parse_flac(reader: &mut UringBufReader) -> Result<AudioFileMeta, Co
             .to_string_lossy()
             .to_string(),
         format: Some("flac".to_owned()),
+        mtime: None,
+        size: None,
+        audio_hash: None,
     };
     let mut vorbis_sections = Vec::new();
     let mut pictures = Vec::new();
     let mut paddings = Vec::new();
-    loop {
-        let header = reader.get_bytes(4).await?;
-        let block_length = u32::from_be_bytes([0, header[1], header[2], header[3]]) as usize;
+    let mut blobs = Vec::new();
+    let mut stream_info = None;
+    let mut seek_points = Vec::new();
+    let mut cue_sheet = None;
 
-        match header[0] {
-            VORBIS_COMMENT_MARKER::MARKER => {
-                let vorbis_ptr = (reader.file_ptr + reader.cursor) as i64;
-                let vorbis_block = reader.get_bytes(block_length).await?;
-                if vorbis_block.len() < block_length {
-                    return Err(Corruption {
-                        message: format!(
-                            "Not enough bytes for vorbis block. Length: {block_length}"
-                        ),
-                        file_cursor: reader.current_offset(),
-                        path: reader.path.to_owned(),
-                    });
+    let mut blocks = Box::pin(parse_flac_stream(reader));
+    while let Some(block) = blocks.next().await {
+        match block? {
+            MetadataBlock::StreamInfo(info) => stream_info = Some(info),
+            MetadataBlock::VorbisComment(meta, comments) => {
+                vorbis_sections.push((meta, comments))
+            }
+            MetadataBlock::Picture(picture, blob) => {
+                pictures.push(picture);
+                blobs.extend(blob);
+            }
+            MetadataBlock::Padding(padding) => paddings.push(padding),
+            MetadataBlock::SeekTable(points) => seek_points.extend(points),
+            MetadataBlock::CueSheet(sheet, tracks) => cue_sheet = Some((sheet, tracks)),
+            MetadataBlock::Unknown { .. } => {}
+        }
+    }
+
+    Ok(AudioFileMeta {
+        audio_file,
+        comments: vorbis_sections,
+        pictures,
+        paddings,
+        blobs,
+        opus: None,
+        stream_info,
+        seek_points,
+        cue_sheet,
+    })
+}
+
+/// Walks a FLAC file's metadata blocks one at a time instead of eagerly
+/// collecting all of them, so blocks after the point where a caller stops
+/// polling are never read. Each yielded `MetadataBlock` has already been
+/// fully read off `reader` (a PICTURE block's image bytes included), so the stream
+/// is always safe to resume from wherever the previous item left off.
+pub fn parse_flac_stream(
+    reader: &mut UringBufReader,
+) -> impl Stream<Item = Result<MetadataBlock, Corruption>> + '_ {
+    try_stream! {
+        loop {
+            let header = reader.get_bytes(4).await?;
+            let block_length = u32::from_be_bytes([0, header[1], header[2], header[3]]) as usize;
+
+            match header[0] {
+                STREAMINFO_MARKER::MARKER => {
+                    yield MetadataBlock::StreamInfo(parse_stream_info(reader, block_length).await?);
                 }
+                STREAMINFO_MARKER::END_OF_BLOCK => {
+                    yield MetadataBlock::StreamInfo(parse_stream_info(reader, block_length).await?);
+                    break;
+                }
+                VORBIS_COMMENT_MARKER::MARKER => {
+                    let vorbis_ptr = (reader.file_ptr + reader.cursor) as i64;
+                    let vorbis_block = reader.get_bytes(block_length).await?;
+                    if vorbis_block.len() < block_length {
+                        Err(Corruption {
+                            message: format!(
+                                "Not enough bytes for vorbis block. Length: {block_length}"
+                            ),
+                            file_cursor: reader.current_offset(),
+                            path: reader.path.to_owned(),
+                            cause: None,
+                        })?;
+                    }
 
-                vorbis_sections.push(
-                    VorbisComment::parse_block(vorbis_block, vorbis_ptr)
+                    let (meta, comments) = VorbisComment::parse_block(vorbis_block, vorbis_ptr)
                         .await
                         .map_err(|mut err| {
                             err.path = reader.path.to_owned();
                             err
-                        })?,
-                );
-            }
-            VORBIS_COMMENT_MARKER::END_OF_BLOCK => {
-                let vorbis_ptr = reader.current_offset() as i64;
-                let vorbis_block = reader.get_bytes(block_length).await?;
-
-                if vorbis_block.len() < block_length {
-                    return Err(Corruption {
-                        message: format!(
-                            "Not enough bytes for vorbis block. Length: {block_length}"
-                        ),
-                        file_cursor: reader.current_offset(),
-                        path: reader.path.to_owned(),
-                    });
+                        })?;
+                    yield MetadataBlock::VorbisComment(meta, comments);
                 }
+                VORBIS_COMMENT_MARKER::END_OF_BLOCK => {
+                    let vorbis_ptr = reader.current_offset() as i64;
+                    let vorbis_block = reader.get_bytes(block_length).await?;
+
+                    if vorbis_block.len() < block_length {
+                        Err(Corruption {
+                            message: format!(
+                                "Not enough bytes for vorbis block. Length: {block_length}"
+                            ),
+                            file_cursor: reader.current_offset(),
+                            path: reader.path.to_owned(),
+                            cause: None,
+                        })?;
+                    }
 
-                vorbis_sections.push(
-                    VorbisComment::parse_block(vorbis_block, vorbis_ptr)
+                    let (meta, comments) = VorbisComment::parse_block(vorbis_block, vorbis_ptr)
                         .await
                         .map_err(|mut err| {
                             err.path = reader.path.to_owned();
                             err
-                        })?,
-                );
-                break;
-            }
-            PICTURE_MARKER::MARKER => {
-                pictures.push(parse_picture(reader).await?);
-            }
-            PICTURE_MARKER::END_OF_BLOCK => {
-                pictures.push(parse_picture(reader).await?);
-                break;
-            }
-            PADDING_MARKER::MARKER => {
-                paddings.push(Padding {
-                    id: None,
-                    file_id: None,
-                    file_ptr: Some(reader.current_offset() as i64),
-                    byte_size: Some(block_length as i64),
-                });
-                reader.skip(block_length as u64).await?;
-            }
-            PADDING_MARKER::END_OF_BLOCK => {
-                paddings.push(Padding {
-                    id: None,
-                    file_id: None,
-                    file_ptr: Some(reader.current_offset() as i64),
-                    byte_size: Some(block_length as i64),
-                });
-
-                break;
-            }
-            n if n >= 128 => {
-                // reached end marker
-                break;
-            }
-            _ => {
-                // ignored block
-                reader.skip(block_length as u64).await?;
+                        })?;
+                    yield MetadataBlock::VorbisComment(meta, comments);
+                    break;
+                }
+                PICTURE_MARKER::MARKER => {
+                    let (picture, blob) = parse_picture(reader).await?;
+                    yield MetadataBlock::Picture(picture, blob);
+                }
+                PICTURE_MARKER::END_OF_BLOCK => {
+                    let (picture, blob) = parse_picture(reader).await?;
+                    yield MetadataBlock::Picture(picture, blob);
+                    break;
+                }
+                PADDING_MARKER::MARKER => {
+                    let padding = Padding {
+                        id: None,
+                        file_id: None,
+                        file_ptr: Some(reader.current_offset() as i64),
+                        byte_size: Some(block_length as i64),
+                    };
+                    reader.skip(block_length as u64).await?;
+                    yield MetadataBlock::Padding(padding);
+                }
+                PADDING_MARKER::END_OF_BLOCK => {
+                    yield MetadataBlock::Padding(Padding {
+                        id: None,
+                        file_id: None,
+                        file_ptr: Some(reader.current_offset() as i64),
+                        byte_size: Some(block_length as i64),
+                    });
+                    break;
+                }
+                SEEKTABLE_MARKER::MARKER => {
+                    let block = reader.get_bytes(block_length).await?;
+                    yield MetadataBlock::SeekTable(SeekPoint::parse_block(block));
+                }
+                SEEKTABLE_MARKER::END_OF_BLOCK => {
+                    let block = reader.get_bytes(block_length).await?;
+                    yield MetadataBlock::SeekTable(SeekPoint::parse_block(block));
+                    break;
+                }
+                CUESHEET_MARKER::MARKER => {
+                    let block_ptr = (reader.file_ptr + reader.cursor) as i64;
+                    let block = reader.get_bytes(block_length).await?;
+                    let (sheet, tracks) =
+                        CueSheet::parse_block(block, block_ptr).map_err(|mut err| {
+                            err.path = reader.path.to_owned();
+                            err
+                        })?;
+                    yield MetadataBlock::CueSheet(sheet, tracks);
+                }
+                CUESHEET_MARKER::END_OF_BLOCK => {
+                    let block_ptr = (reader.file_ptr + reader.cursor) as i64;
+                    let block = reader.get_bytes(block_length).await?;
+                    let (sheet, tracks) =
+                        CueSheet::parse_block(block, block_ptr).map_err(|mut err| {
+                            err.path = reader.path.to_owned();
+                            err
+                        })?;
+                    yield MetadataBlock::CueSheet(sheet, tracks);
+                    break;
+                }
+                n if n >= 128 => {
+                    // reached end marker
+                    break;
+                }
+                n => {
+                    // unknown block, skip past it but still surface it
+                    reader.skip(block_length as u64).await?;
+                    yield MetadataBlock::Unknown { marker: n, length: block_length };
+                }
             }
         }
     }
-    Ok(AudioFileMeta {
-        audio_file,
-        comments: vorbis_sections,
-        pictures,
-        paddings,
-    })
 }
-async fn parse_picture(reader: &mut UringBufReader) -> Result<Picture, Corruption> {
+
+/// Parses a FLAC STREAMINFO block body (block type `0`, fixed 34 bytes):
+/// the only place a FLAC file records its sample rate, channel count, bit
+/// depth, duration, and the MD5 of the unencoded audio.
+async fn parse_stream_info(
+    reader: &mut UringBufReader,
+    block_length: usize,
+) -> Result<StreamInfo, Corruption> {
+    let block = reader.get_bytes(block_length).await?;
+    if block.len() < 34 {
+        return Err(Corruption {
+            message: format!("Not enough bytes for STREAMINFO block. Length: {block_length}"),
+            file_cursor: reader.current_offset(),
+            path: reader.path.to_owned(),
+            cause: None,
+        });
+    }
+    Ok(StreamInfo::parse(block))
+}
+
+/// Parses a FLAC PICTURE block, extracting and content-hashing the embedded
+/// image so identical cover art shared across an album's tracks is stored
+/// only once (see `VorbisBlob`).
+async fn parse_picture(
+    reader: &mut UringBufReader,
+) -> Result<(Picture, Option<VorbisBlob>), Corruption> {
     let file_ptr = reader.current_offset() as i64;
     let picture_type = reader.read_u32().await?;
 
@@ -156,20 +328,351 @@ async fn parse_picture(reader: &mut UringBufReader) -> Result<Picture, Corruptio
     let indexed_color_number = reader.read_u32().await?;
     let picture_len = reader.read_u32().await?;
 
-    reader.skip(picture_len as u64).await?;
+    let image_bytes = reader.get_bytes(picture_len as usize).await?;
+    let blob = VorbisBlob::new(image_bytes.to_vec());
+    let blob_hash = Some(blob.hash.clone());
 
-    Ok(Picture {
-        id: None,
-        file_id: None,
-        file_ptr,
-        picture_type: picture_type as i64,
-        size: picture_len as i64,
-        mime,
-        description,
-        width: width as i64,
-        height: height as i64,
-        color_depth: color_depth as i64,
-        indexed_color_number: indexed_color_number as i64,
-        vorbis_comment: false,
+    if let Some(mismatch) = check_mime_magic(&mime, &blob.data) {
+        println!("picture at offset {file_ptr} declares mime {mime:?} but its data looks like {mismatch}");
+    }
+
+    Ok((
+        Picture {
+            id: None,
+            file_id: None,
+            file_ptr,
+            picture_type: picture_type as i64,
+            size: picture_len as i64,
+            mime,
+            description,
+            width: width as i64,
+            height: height as i64,
+            color_depth: color_depth as i64,
+            indexed_color_number: indexed_color_number as i64,
+            vorbis_comment: false,
+            blob_hash,
+            last_ogg_header_ptr: None,
+        },
+        Some(blob),
+    ))
+}
+
+/// Tells a caller whether `write_vorbis_comment` managed to patch the
+/// existing VORBIS_COMMENT/PADDING blocks in place, or had to fall back to
+/// rewriting the whole file because the new tags no longer fit.
+#[derive(Debug, PartialEq, Eq)]
+pub enum CommentWrite {
+    /// patched in place; `remaining_padding` is the new PADDING block's
+    /// `byte_size` if one still follows the comment block, or `None` if the
+    /// padding run was fully consumed and its row should be dropped
+    InPlace { remaining_padding: Option<i64> },
+    /// didn't fit in the existing comment block + following padding run, so
+    /// the rest of the file was already copied forward and truncated to its
+    /// new size (see `rewrite_flac_comment`) by the time this is returned
+    Rewritten,
+}
+
+/// Rewrites the VORBIS_COMMENT block described by `vorbis_meta` in place,
+/// reusing the PADDING block that immediately follows it (if any) to absorb
+/// the size difference, per the same trick FLAC tag editors use to avoid
+/// rewriting the whole file for a tag edit.
+pub async fn write_vorbis_comment(
+    reader: &mut UringBufReader,
+    vorbis_meta: &VorbisMeta,
+    padding: Option<&Padding>,
+    vendor: &str,
+    comments: &[(String, String)],
+) -> Result<CommentWrite, Corruption> {
+    let new_body = VorbisComment::serialize_block(vendor, comments);
+    let header_ptr = vorbis_meta.file_ptr as u64 - 4;
+    let old_body_len = (vorbis_meta.end_ptr - vorbis_meta.file_ptr) as usize;
+
+    let following_padding =
+        padding.filter(|p| p.file_ptr.map(|ptr| ptr - 4) == Some(vorbis_meta.end_ptr));
+
+    let (is_last, remaining_padding) = match following_padding {
+        Some(padding) => {
+            // old comment body + padding's header and body is all the room we have to work with
+            let room = old_body_len + 4 + padding.byte_size.unwrap_or(0) as usize;
+            if new_body.len() > room {
+                rewrite_flac_comment(reader, vorbis_meta, vendor, comments).await?;
+                return Ok(CommentWrite::Rewritten);
+            }
+
+            let padding_header_ptr = padding.file_ptr.unwrap() as u64 - 4;
+            let padding_header = reader.get_bytes_at(1, padding_header_ptr).await?;
+            let padding_was_last = padding_header[0] & LAST_BLOCK_FLAG != 0;
+
+            let leftover = room - new_body.len();
+            if leftover > 0 && leftover < 4 {
+                // 1-3 spare bytes can't hold a padding block header and would
+                // be left stranded between blocks, so rewrite instead
+                rewrite_flac_comment(reader, vorbis_meta, vendor, comments).await?;
+                return Ok(CommentWrite::Rewritten);
+            } else if leftover == 0 {
+                // the new comment block fills the room exactly: padding is fully consumed
+                (padding_was_last, None)
+            } else {
+                let new_padding_ptr = header_ptr + 4 + new_body.len() as u64;
+                let new_padding_len = leftover - 4;
+                let mut new_padding_header = Vec::with_capacity(4);
+                new_padding_header.push(if padding_was_last {
+                    LAST_BLOCK_FLAG | 1
+                } else {
+                    1
+                });
+                new_padding_header.extend(&(new_padding_len as u32).to_be_bytes()[1..4]);
+                reader.write_at(new_padding_ptr, new_padding_header).await?;
+                (false, Some(new_padding_len as i64))
+            }
+        }
+        None => {
+            if new_body.len() != old_body_len {
+                rewrite_flac_comment(reader, vorbis_meta, vendor, comments).await?;
+                return Ok(CommentWrite::Rewritten);
+            }
+            let old_header = reader.get_bytes_at(1, header_ptr).await?;
+            (old_header[0] & LAST_BLOCK_FLAG != 0, None)
+        }
+    };
+
+    let mut block = Vec::with_capacity(4 + new_body.len());
+    block.push(if is_last { LAST_BLOCK_FLAG | 4 } else { 4 });
+    block.extend(&(new_body.len() as u32).to_be_bytes()[1..4]);
+    block.extend(new_body);
+    reader.write_at(header_ptr, block).await?;
+
+    Ok(CommentWrite::InPlace { remaining_padding })
+}
+
+/// Fallback for when the new VORBIS_COMMENT block no longer fits in the old
+/// comment block + following padding run: splices the new block in at the
+/// old header's offset, then copies everything that followed the old block
+/// (remaining metadata blocks, audio frames) to its new offset and truncates
+/// the file to the new total size. Mirrors the Ogg writer's own
+/// copy-and-truncate fallback (`opus_ogg::rewrite_comment_block`). Requires
+/// `reader` to know the file's total size (see `UringBufReader::with_file_size`),
+/// since the copy has to run tail-first when the new block is bigger than the
+/// old one, to avoid a shifted write clobbering source bytes it hasn't read yet.
+async fn rewrite_flac_comment(
+    reader: &mut UringBufReader,
+    vorbis_meta: &VorbisMeta,
+    vendor: &str,
+    comments: &[(String, String)],
+) -> Result<(), Corruption> {
+    let new_body = VorbisComment::serialize_block(vendor, comments);
+    let header_ptr = vorbis_meta.file_ptr as u64 - 4;
+
+    let old_header = reader.get_bytes_at(1, header_ptr).await?;
+    let is_last = old_header[0] & LAST_BLOCK_FLAG != 0;
+
+    let mut block = Vec::with_capacity(4 + new_body.len());
+    block.push(if is_last { LAST_BLOCK_FLAG | 4 } else { 4 });
+    block.extend(&(new_body.len() as u32).to_be_bytes()[1..4]);
+    block.extend(new_body);
+    let block_len = block.len() as u64;
+
+    let old_end_ptr = vorbis_meta.end_ptr as u64;
+    let new_end_ptr = header_ptr + block_len;
+    let delta = new_end_ptr as i64 - old_end_ptr as i64;
+
+    let file_size = reader.file_size.ok_or_else(|| Corruption {
+        message: "rewrite_flac_comment requires a reader with a known file size".to_owned(),
+        file_cursor: header_ptr,
+        path: reader.path.to_owned(),
+        cause: None,
+    })?;
+    let trailing = file_size.saturating_sub(old_end_ptr);
+
+    // copy the trailing region (remaining blocks + audio frames) to its new
+    // offset *before* touching the comment block itself: growing shifts it
+    // into territory the new block is about to overwrite, so it has to be
+    // moved out of the way first. growing copies tail-first, so a chunk's
+    // shifted write never lands on source bytes a later iteration still
+    // needs to read; shrinking only ever shifts writes behind their reads,
+    // so a head-first pass is safe there.
+    let mut copied = 0u64;
+    while copied < trailing {
+        let chunk = (REWRITE_COPY_CHUNK as u64).min(trailing - copied) as usize;
+        let src = if delta > 0 {
+            old_end_ptr + trailing - copied - chunk as u64
+        } else {
+            old_end_ptr + copied
+        };
+        let dst = (src as i64 + delta) as u64;
+
+        let buf = vec![0u8; chunk];
+        let (res, buf) = reader.file.read_at(buf, src).await;
+        res.map_err(|err| Corruption::io(reader.path.to_owned(), src, err))?;
+        reader.write_at(dst, buf).await?;
+        copied += chunk as u64;
+    }
+
+    reader.write_at(header_ptr, block).await?;
+
+    let total_size = (file_size as i64 + delta) as u64;
+    unsafe {
+        let fd = reader.file.as_raw_fd();
+        libc::ftruncate64(fd, total_size as i64);
+    }
+    reader
+        .file
+        .sync_all()
+        .await
+        .map_err(|err| Corruption::io(reader.path.to_owned(), total_size, err))?;
+
+    Ok(())
+}
+
+/// Parses an OggFLAC logical bitstream (`.oga`, or `.ogg` wrapping FLAC
+/// instead of Vorbis/Opus) into the same `AudioFileMeta` shape as native
+/// FLAC. Each header block is read off `ogg_reader` the same way
+/// `parse_flac_stream` reads one off a plain `UringBufReader` — a 4-byte
+/// `[marker][24-bit length]` header, then that many body bytes — relying on
+/// `OggPageReader::get_bytes` to transparently cross page boundaries, the
+/// same way it already does for a multi-page Vorbis comment. `first_packet`
+/// is the already-read mapping header packet (the 0x7F "FLAC" marker,
+/// version, header packet count, native `fLaC` signature, and the
+/// STREAMINFO block it wraps).
+pub async fn parse_ogg_flac<'a>(
+    ogg_reader: &mut OggPageReader<'a>,
+    first_packet: &[u8],
+) -> Result<AudioFileMeta, Corruption> {
+    if first_packet.len() < OGG_FLAC_HEADER_PREFIX_LEN + 4 {
+        return Err(Corruption {
+            path: ogg_reader.reader.path.to_owned(),
+            message: format!(
+                "Not enough bytes for OggFLAC mapping header. Length: {}",
+                first_packet.len()
+            ),
+            file_cursor: ogg_reader.reader.current_offset(),
+            cause: None,
+        });
+    }
+
+    let marker = first_packet[OGG_FLAC_HEADER_PREFIX_LEN];
+    let block_length = u32::from_be_bytes([
+        0,
+        first_packet[OGG_FLAC_HEADER_PREFIX_LEN + 1],
+        first_packet[OGG_FLAC_HEADER_PREFIX_LEN + 2],
+        first_packet[OGG_FLAC_HEADER_PREFIX_LEN + 3],
+    ]) as usize;
+    let body_start = OGG_FLAC_HEADER_PREFIX_LEN + 4;
+    if block_length < 34 || first_packet.len() < body_start + block_length {
+        return Err(Corruption {
+            path: ogg_reader.reader.path.to_owned(),
+            message: format!(
+                "Not enough bytes for OggFLAC mapping header's STREAMINFO block. Length: {block_length}"
+            ),
+            file_cursor: ogg_reader.reader.current_offset(),
+            cause: None,
+        });
+    }
+    let stream_info = StreamInfo::parse(&first_packet[body_start..body_start + block_length]);
+
+    let mut vorbis_sections = Vec::new();
+    let mut pictures = Vec::new();
+    let mut paddings = Vec::new();
+    let mut blobs = Vec::new();
+    let mut seek_points = Vec::new();
+    let mut cue_sheet = None;
+
+    let mut is_last = marker & LAST_BLOCK_FLAG != 0;
+    while !is_last {
+        let block_ptr = ogg_reader.reader.current_offset() as i64;
+        let header = ogg_reader.get_bytes(4).await?;
+        let marker = header[0];
+        is_last = marker & LAST_BLOCK_FLAG != 0;
+        let block_length = u32::from_be_bytes([0, header[1], header[2], header[3]]) as usize;
+        let body = ogg_reader.get_bytes(block_length).await?;
+
+        match parse_ogg_flac_block(marker, body, block_ptr).await? {
+            MetadataBlock::VorbisComment(meta, comments) => vorbis_sections.push((meta, comments)),
+            MetadataBlock::Picture(picture, blob) => {
+                pictures.push(picture);
+                blobs.extend(blob);
+            }
+            MetadataBlock::Padding(padding) => paddings.push(padding),
+            MetadataBlock::SeekTable(points) => seek_points.extend(points),
+            MetadataBlock::CueSheet(sheet, tracks) => cue_sheet = Some((sheet, tracks)),
+            MetadataBlock::StreamInfo(_) | MetadataBlock::Unknown { .. } => {}
+        }
+    }
+
+    Ok(AudioFileMeta {
+        audio_file: AudioFile {
+            id: None,
+            path: ogg_reader.reader.path.to_string_lossy().to_string(),
+            name: ogg_reader
+                .reader
+                .path
+                .file_name()
+                .unwrap()
+                .to_string_lossy()
+                .to_string(),
+            format: Some("oga".to_owned()),
+            mtime: None,
+            size: None,
+            audio_hash: None,
+        },
+        comments: vorbis_sections,
+        pictures,
+        paddings,
+        blobs,
+        opus: None,
+        stream_info: Some(stream_info),
+        seek_points,
+        cue_sheet,
+    })
+}
+
+/// Dispatches one already-depaginated OggFLAC header packet to the same
+/// per-block-type parsing native FLAC uses, keyed by the same marker bit
+/// layout (`marker & !LAST_BLOCK_FLAG` gives the base block type regardless
+/// of whether this was the stream's last header packet).
+async fn parse_ogg_flac_block(
+    marker: u8,
+    body: Vec<u8>,
+    block_ptr: i64,
+) -> Result<MetadataBlock, Corruption> {
+    Ok(match marker & !LAST_BLOCK_FLAG {
+        STREAMINFO_MARKER::MARKER => {
+            if body.len() < 34 {
+                return Err(Corruption {
+                    message: format!(
+                        "Not enough bytes for STREAMINFO block. Length: {}",
+                        body.len()
+                    ),
+                    file_cursor: block_ptr as u64,
+                    path: "".into(),
+                    cause: None,
+                });
+            }
+            MetadataBlock::StreamInfo(StreamInfo::parse(&body))
+        }
+        VORBIS_COMMENT_MARKER::MARKER => {
+            let (meta, comments) = VorbisComment::parse_block(body, block_ptr).await?;
+            MetadataBlock::VorbisComment(meta, comments)
+        }
+        PICTURE_MARKER::MARKER => {
+            let (picture, blob) =
+                Picture::from_picture_block_with_data(&body, block_ptr, false, None)?;
+            MetadataBlock::Picture(picture, blob)
+        }
+        PADDING_MARKER::MARKER => MetadataBlock::Padding(Padding {
+            id: None,
+            file_id: None,
+            file_ptr: Some(block_ptr),
+            byte_size: Some(body.len() as i64),
+        }),
+        SEEKTABLE_MARKER::MARKER => MetadataBlock::SeekTable(SeekPoint::parse_block(&body)),
+        CUESHEET_MARKER::MARKER => {
+            let (sheet, tracks) = CueSheet::parse_block(&body, block_ptr)?;
+            MetadataBlock::CueSheet(sheet, tracks)
+        }
+        n => MetadataBlock::Unknown {
+            marker: n,
+            length: body.len(),
+        },
     })
 }
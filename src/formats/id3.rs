@@ -0,0 +1,158 @@
+use crate::{
+    db::{
+        audio_file::{AudioFile, AudioFileMeta},
+        vorbis::{VorbisComment, VorbisMeta},
+    },
+    io::reader::{Corruption, UringBufReader},
+};
+
+pub const ID3_MARKER: [u8; 3] = [0x49, 0x44, 0x33]; // "ID3"
+
+/// Maps the handful of ID3v2 text frames players actually rely on onto the
+/// same key names Vorbis comments use, so both formats land in the same
+/// `vorbis_comments` table without the rest of the pipeline knowing the
+/// difference.
+const FRAME_KEY_MAP: [(&[u8; 4], &str); 5] = [
+    (b"TIT2", "title"),
+    (b"TPE1", "artist"),
+    (b"TALB", "album"),
+    (b"TRCK", "tracknumber"),
+    (b"TDRC", "date"),
+];
+
+fn frame_key(frame_id: &[u8; 4]) -> Option<&'static str> {
+    FRAME_KEY_MAP
+        .iter()
+        .find(|(id, _)| *id == frame_id)
+        .map(|(_, key)| *key)
+}
+
+/// A synchsafe integer: 4 bytes, each holding 7 usable bits with the high
+/// bit always unset, used for the ID3v2 header size and (from v2.4 on)
+/// frame sizes, so the sync signal `0xFF` can never appear in a length.
+fn synchsafe_u32(bytes: [u8; 4]) -> u32 {
+    bytes
+        .iter()
+        .fold(0u32, |acc, &b| (acc << 7) | (b & 0x7f) as u32)
+}
+
+/// Decodes a text frame body: a 1-byte encoding marker followed by the
+/// text itself. Malformed UTF-16 code units are replaced with U+FFFD
+/// rather than failing the whole file over one bad frame.
+fn decode_text_frame(body: &[u8]) -> String {
+    let Some((&encoding, text)) = body.split_first() else {
+        return String::new();
+    };
+
+    let decoded = match encoding {
+        0 => text.iter().map(|&b| b as char).collect(),
+        3 => String::from_utf8_lossy(text).into_owned(),
+        _ => {
+            let big_endian = text.starts_with(&[0xfe, 0xff]);
+            let text = if text.starts_with(&[0xff, 0xfe]) || big_endian {
+                &text[2..]
+            } else {
+                text
+            };
+            let units = text.chunks_exact(2).map(|pair| {
+                if big_endian {
+                    u16::from_be_bytes([pair[0], pair[1]])
+                } else {
+                    u16::from_le_bytes([pair[0], pair[1]])
+                }
+            });
+            char::decode_utf16(units)
+                .map(|r| r.unwrap_or('\u{fffd}'))
+                .collect()
+        }
+    };
+
+    decoded.trim_end_matches('\0').to_string()
+}
+
+/// Parses an ID3v2 tag: the 10-byte header (3-byte "ID3" magic, version,
+/// flags, synchsafe size) followed by frames, each a 10-byte frame header
+/// plus body. Only the handful of text frames in `FRAME_KEY_MAP` are kept;
+/// everything else (pictures, comments, binary frames) is skipped for now.
+///
+/// `major_version` is the 4th header byte, already consumed by the caller
+/// while sniffing the leading magic bytes (see `read_with_uring`).
+pub async fn parse_id3(
+    reader: &mut UringBufReader,
+    major_version: u8,
+) -> Result<AudioFileMeta, Corruption> {
+    let tag_ptr = reader.current_offset() as i64 - ID3_MARKER.len() as i64 - 1;
+
+    let audio_file = AudioFile {
+        id: None,
+        path: reader.path.to_string_lossy().to_string(),
+        name: reader
+            .path
+            .file_name()
+            .unwrap()
+            .to_string_lossy()
+            .to_string(),
+        format: Some("mp3".to_owned()),
+        mtime: None,
+        size: None,
+        audio_hash: None,
+    };
+
+    // revision (1 byte) + flags (1 byte) + synchsafe size (4 bytes)
+    let header = reader.get_bytes(6).await?;
+    let size = synchsafe_u32([header[2], header[3], header[4], header[5]]);
+
+    let mut comments = Vec::new();
+    let mut remaining = size as i64;
+
+    while remaining > 10 {
+        let frame_header = reader.get_bytes(10).await?;
+        let frame_id: [u8; 4] = frame_header[0..4].try_into().unwrap();
+        if frame_id == [0, 0, 0, 0] {
+            // padding reached before the declared tag size ran out
+            reader.skip(remaining as u64 - 10).await?;
+            break;
+        }
+        let frame_size = if major_version >= 4 {
+            synchsafe_u32(frame_header[4..8].try_into().unwrap())
+        } else {
+            u32::from_be_bytes(frame_header[4..8].try_into().unwrap())
+        };
+
+        let body = reader.get_bytes(frame_size as usize).await?;
+        if let Some(key) = frame_key(&frame_id) {
+            comments.push(VorbisComment {
+                id: None,
+                meta_id: None,
+                key: key.to_owned(),
+                file_ptr: 0,
+                last_ogg_header_ptr: None,
+                size: frame_size as i64,
+                value: Some(decode_text_frame(body)),
+            });
+        }
+
+        remaining -= 10 + frame_size as i64;
+    }
+
+    let vorbis_meta = VorbisMeta {
+        id: None,
+        file_id: None,
+        file_ptr: tag_ptr,
+        end_ptr: tag_ptr + 10 + size as i64,
+        comment_amount_ptr: tag_ptr,
+        vendor: format!("ID3v2.{major_version}"),
+    };
+
+    Ok(AudioFileMeta {
+        audio_file,
+        comments: vec![(vorbis_meta, comments)],
+        pictures: Vec::new(),
+        paddings: Vec::new(),
+        blobs: Vec::new(),
+        opus: None,
+        stream_info: None,
+        seek_points: Vec::new(),
+        cue_sheet: None,
+    })
+}
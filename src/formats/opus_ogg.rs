@@ -1,22 +1,31 @@
 use crate::{
     db::{
         audio_file::{AudioFile, AudioFileMeta},
+        opus::OpusMeta,
         padding::Padding,
         picture::Picture,
         vorbis::{VorbisComment, VorbisMeta},
     },
-    io::{ogg::OggPageReader, reader::UringBufReader},
+    io::{
+        backend::{FileBackend, FileHandle},
+        ogg::{build_packet_pages, copy_and_renumber_pages, OggPageReader},
+        reader::{Corruption, UringBufReader},
+    },
 };
+use super::flac::{parse_ogg_flac, OGG_FLAC_MARKER};
 use base64::{engine::general_purpose, Engine as _};
 use std::os::fd::AsRawFd;
-use tokio_uring::fs::OpenOptions;
 
 pub const OGG_MARKER: [u8; 4] = [0x4F, 0x67, 0x67, 0x53];
 const MAX_OGG_PAGE_SIZE: u32 = 65_307;
 const VORBIS_SIZE_LIMIT: u32 = MAX_OGG_PAGE_SIZE; // skips values of any comments > this size
 const OPUS_MARKER: [u8; 8] = [0x4F, 0x70, 0x75, 0x73, 0x48, 0x65, 0x61, 0x64];
 const OPUS_TAGS_MARKER: [u8; 8] = [0x4F, 0x70, 0x75, 0x73, 0x54, 0x61, 0x67, 0x73];
-const VORBIS_PICTURE_MARKER: [u8; 22] = [
+// packet type byte (0x01) + "vorbis", identifies the Vorbis identification header
+const VORBIS_IDENTIFICATION_MARKER: [u8; 7] = [0x01, 0x76, 0x6F, 0x72, 0x62, 0x69, 0x73];
+// packet type byte (0x03) + "vorbis", identifies the Vorbis comment header
+const VORBIS_COMMENT_HEADER_MARKER: [u8; 7] = [0x03, 0x76, 0x6F, 0x72, 0x62, 0x69, 0x73];
+pub const VORBIS_PICTURE_MARKER: [u8; 22] = [
     0x6D, 0x65, 0x74, 0x61, 0x64, 0x61, 0x74, 0x61, 0x5F, 0x62, 0x6C, 0x6F, 0x63, 0x6B, 0x5F, 0x70,
     0x69, 0x63, 0x74, 0x75, 0x72, 0x65,
 ];
@@ -27,10 +36,26 @@ const VORBIS_PICTURE_MARKER_UPPER: [u8; 22] = [
 
 async fn parse_opus_vorbis<'a>(
     ogg_reader: &mut OggPageReader<'a>,
-) -> anyhow::Result<AudioFileMeta> {
+    opus_meta: OpusMeta,
+) -> Result<AudioFileMeta, Corruption> {
+    let mut meta = parse_vorbis_comments(ogg_reader, "opus").await?;
+    meta.opus = Some(opus_meta);
+    Ok(meta)
+}
+
+/// Parses a Vorbis comment header, shared by Opus (`OpusTags`) and plain
+/// Vorbis (`vorbis comment header`) streams — once past the packet-specific
+/// magic, both containers lay the body out identically: a little-endian u32
+/// vendor length, the vendor string, a u32 comment count, then each comment
+/// as `[u32 length][key=value]`.
+async fn parse_vorbis_comments<'a>(
+    ogg_reader: &mut OggPageReader<'a>,
+    format: &str,
+) -> Result<AudioFileMeta, Corruption> {
     let mut comments = Vec::new();
     let mut pictures = Vec::new();
     let mut padding = Vec::new();
+    let mut blobs = Vec::new();
 
     let vorbis_ptr = ogg_reader.reader.current_offset() as i64;
 
@@ -83,8 +108,9 @@ async fn parse_opus_vorbis<'a>(
                 let skipped = if comment_key == VORBIS_PICTURE_MARKER
                     || comment_key == VORBIS_PICTURE_MARKER_UPPER
                 {
+                    let header_ptr = ogg_reader.last_header_ptr as i64;
                     let (skipped, picture) =
-                        parse_picture_meta(ogg_reader, comment_ptr as i64).await?;
+                        parse_picture_meta(ogg_reader, comment_ptr as i64, header_ptr).await?;
 
                     pictures.push(picture);
                     skipped
@@ -92,6 +118,9 @@ async fn parse_opus_vorbis<'a>(
                     0
                 };
 
+                ogg_reader
+                    .reader
+                    .check_declared_len(comment_len as usize, ogg_reader.reader.current_offset())?;
                 ogg_reader.reader.extend_buf(comment_len as usize).await?;
                 ogg_reader
                     .safe_skip(comment_len as usize - comment_key.len() - skipped as usize - 1)
@@ -102,13 +131,16 @@ async fn parse_opus_vorbis<'a>(
                     if picture_check == VORBIS_PICTURE_MARKER
                         || picture_check == VORBIS_PICTURE_MARKER_UPPER
                     {
-                        pictures.push(Picture::from_picture_block(
+                        let (picture, blob) = Picture::from_picture_block_with_data(
                             &general_purpose::STANDARD
                                 .decode(&comment[VORBIS_PICTURE_MARKER.len() + 1..])
                                 .unwrap(),
                             comment_ptr as i64,
                             true,
-                        ));
+                            Some(ogg_reader.last_header_ptr as i64),
+                        )?;
+                        pictures.push(picture);
+                        blobs.extend(blob);
                     }
                 }
                 if let Some((key, val)) = VorbisComment::into_key_val(&comment) {
@@ -179,18 +211,27 @@ async fn parse_opus_vorbis<'a>(
                 .unwrap()
                 .to_string_lossy()
                 .to_string(),
-            format: Some("opus".to_owned()),
+            format: Some(format.to_owned()),
+            mtime: None,
+            size: None,
+            audio_hash: None,
         },
         pictures,
         comments: vec![(meta, comments)],
         paddings: padding,
+        blobs,
+        opus: None,
+        stream_info: None,
+        seek_points: Vec::new(),
+        cue_sheet: None,
     })
 }
 
 async fn parse_picture_meta<'a>(
     ogg_reader: &mut OggPageReader<'a>,
     file_ptr: i64,
-) -> anyhow::Result<(u32, Picture)> {
+    last_ogg_header_ptr: i64,
+) -> Result<(u32, Picture), Corruption> {
     let mut size_read = 0;
     let mut final_bytes = Vec::new();
     let to_base64_bytes = |bytes: usize| -> usize {
@@ -237,20 +278,38 @@ async fn parse_picture_meta<'a>(
 
     Ok((
         size_read as u32,
-        Picture::from_picture_block(&final_bytes, file_ptr, true),
+        Picture::from_picture_block(&final_bytes, file_ptr, true, Some(last_ogg_header_ptr))?,
     ))
 }
 
-pub async fn parse_ogg_pages(reader: &mut UringBufReader) -> anyhow::Result<AudioFileMeta> {
+/// Walks every page of an Ogg stream recomputing and checking its CRC32,
+/// without parsing any comments/pictures — a lightweight integrity pass a
+/// caller can run before trusting page boundaries for something like
+/// `remove_comments`, which otherwise propagates a corrupt page straight
+/// into the rewritten file. Takes `reader` positioned the same way
+/// `parse_ogg_pages` expects (right after the leading `OggS` marker has
+/// been consumed). Stops at the first read failure, which in the common
+/// case is simply reaching EOF.
+pub async fn verify_ogg_checksums(
+    reader: &mut UringBufReader,
+) -> anyhow::Result<Vec<(u32, u64, u32, u32)>> {
+    reader.cursor -= 4; // Go back to OGGs
+    let mut ogg_reader = OggPageReader::new_with_integrity_check(reader).await?;
+    while ogg_reader.parse_till_end().await.is_ok() {}
+    Ok(ogg_reader.checksum_mismatches)
+}
+
+pub async fn parse_ogg_pages(reader: &mut UringBufReader) -> Result<AudioFileMeta, Corruption> {
     reader.cursor -= 4; // Go back to OGGs
     let mut ogg_reader = OggPageReader::new(reader).await?;
 
     let first_page = ogg_reader.parse_till_end().await?;
 
     if first_page[0..8] == OPUS_MARKER {
+        let opus_meta = OpusMeta::parse(&first_page)?;
         ogg_reader.parse_header().await?;
         if ogg_reader.get_bytes(8).await? == OPUS_TAGS_MARKER {
-            return parse_opus_vorbis(&mut ogg_reader).await;
+            return parse_opus_vorbis(&mut ogg_reader, opus_meta).await;
         }
         Ok(AudioFileMeta {
             audio_file: AudioFile {
@@ -264,17 +323,57 @@ pub async fn parse_ogg_pages(reader: &mut UringBufReader) -> anyhow::Result<Audi
                     .to_string_lossy()
                     .to_string(),
                 id: None,
+                mtime: None,
+                size: None,
+                audio_hash: None,
             },
             paddings: vec![],
             comments: vec![],
             pictures: vec![],
+            blobs: Vec::new(),
+            opus: Some(opus_meta),
+            stream_info: None,
+            seek_points: Vec::new(),
+            cue_sheet: None,
         })
+    } else if first_page[0..7] == VORBIS_IDENTIFICATION_MARKER {
+        ogg_reader.parse_header().await?;
+        if ogg_reader.get_bytes(7).await? == VORBIS_COMMENT_HEADER_MARKER {
+            return parse_vorbis_comments(&mut ogg_reader, "vorbis").await;
+        }
+        Ok(AudioFileMeta {
+            audio_file: AudioFile {
+                path: ogg_reader.reader.path.to_string_lossy().to_string(),
+                format: Some("vorbis".to_owned()),
+                name: ogg_reader
+                    .reader
+                    .path
+                    .file_name()
+                    .unwrap()
+                    .to_string_lossy()
+                    .to_string(),
+                id: None,
+                mtime: None,
+                size: None,
+                audio_hash: None,
+            },
+            paddings: vec![],
+            comments: vec![],
+            pictures: vec![],
+            blobs: Vec::new(),
+            opus: None,
+            stream_info: None,
+            seek_points: Vec::new(),
+            cue_sheet: None,
+        })
+    } else if first_page[0..5] == OGG_FLAC_MARKER {
+        parse_ogg_flac(&mut ogg_reader, &first_page).await
     } else {
-        // TODO
+        let format = "ogg";
         Ok(AudioFileMeta {
             audio_file: AudioFile {
                 path: ogg_reader.reader.path.to_string_lossy().to_string(),
-                format: Some("ogg".to_owned()),
+                format: Some(format.to_owned()),
                 name: ogg_reader
                     .reader
                     .path
@@ -283,19 +382,24 @@ pub async fn parse_ogg_pages(reader: &mut UringBufReader) -> anyhow::Result<Audi
                     .to_string_lossy()
                     .to_string(),
                 id: None,
+                mtime: None,
+                size: None,
+                audio_hash: None,
             },
             paddings: vec![],
             comments: vec![],
             pictures: vec![],
+            blobs: Vec::new(),
+            opus: None,
+            stream_info: None,
+            seek_points: Vec::new(),
+            cue_sheet: None,
         })
     }
 }
 
 pub async fn remove_comments(meta: AudioFileMeta, names: Vec<String>) -> anyhow::Result<()> {
-    let file = OpenOptions::new()
-        .write(true)
-        .read(true)
-        .open(meta.audio_file.path.clone())
+    let file = <FileHandle as FileBackend>::open(std::path::Path::new(&meta.audio_file.path))
         .await
         .unwrap();
     let mut reader = UringBufReader::new(file, meta.audio_file.path.into());
@@ -319,6 +423,78 @@ pub async fn remove_comments(meta: AudioFileMeta, names: Vec<String>) -> anyhow:
             comment_bytes.extend(comment);
         }
     }
+
+    rewrite_comment_block(&mut ogg_reader, vorbis_meta, comment_bytes, kept_comments).await
+}
+
+/// Upserts `upserts` (key/value pairs, keys matched case-insensitively) into
+/// the comments a file already carries: a key that already exists gets its
+/// value replaced in place (keeping its original ordering), anything new is
+/// appended. Oversized values (e.g. a base64-encoded `metadata_block_picture`)
+/// are written the same way as any other comment — the size limit enforced
+/// in `parse_vorbis_comments` only bounds how much of a huge value gets
+/// buffered while reading, not what can be written back out.
+pub async fn write_comments(
+    meta: AudioFileMeta,
+    upserts: Vec<(String, String)>,
+) -> anyhow::Result<()> {
+    let file = <FileHandle as FileBackend>::open(std::path::Path::new(&meta.audio_file.path))
+        .await
+        .unwrap();
+    let mut reader = UringBufReader::new(file, meta.audio_file.path.into());
+    let mut ogg_reader = OggPageReader::new(&mut reader).await.unwrap();
+    ogg_reader.parse_till_end().await.unwrap();
+    ogg_reader.parse_header().await.unwrap();
+    let (vorbis_meta, comments) = &meta.comments[0]; // oggs can contain only 1 meta field
+
+    let mut pending: Vec<(String, String)> = upserts
+        .into_iter()
+        .map(|(key, value)| (key.to_lowercase(), value))
+        .collect();
+
+    let mut comment_bytes = Vec::new();
+    let mut kept_comments: u32 = 0;
+    for comment in comments.iter() {
+        let replacement = pending
+            .iter()
+            .position(|(key, _)| key.eq_ignore_ascii_case(&comment.key));
+        if let Some(index) = replacement {
+            let (key, value) = pending.remove(index);
+            comment_bytes.extend(VorbisComment::serialize_comment(&key, &value));
+        } else {
+            let bytes = comment
+                .to_owned()
+                .into_bytes_ogg(&mut ogg_reader)
+                .await
+                .unwrap();
+            comment_bytes.extend(bytes);
+        }
+        kept_comments += 1;
+    }
+    for (key, value) in pending {
+        comment_bytes.extend(VorbisComment::serialize_comment(&key, &value));
+        kept_comments += 1;
+    }
+
+    rewrite_comment_block(&mut ogg_reader, vorbis_meta, comment_bytes, kept_comments).await
+}
+
+/// Writes `comment_bytes` (already-serialized comments, `kept_comments` of
+/// them) back as the comment header's count + comment list, reusing the old
+/// pages in place when they fit (falling back to `repaginate_comments` when
+/// they don't), then copies over the rest of the stream and truncates the
+/// file to its new size. Shared by every comment mutation — removal,
+/// upserts, whatever comes next — once each has settled on its own final
+/// `comment_bytes` + `kept_comments`.
+async fn rewrite_comment_block<'a>(
+    ogg_reader: &mut OggPageReader<'a>,
+    vorbis_meta: &VorbisMeta,
+    comment_bytes: Vec<u8>,
+    kept_comments: u32,
+) -> anyhow::Result<()> {
+    let new_payload_len = 4 + comment_bytes.len();
+    let old_capacity = (vorbis_meta.end_ptr - vorbis_meta.comment_amount_ptr) as usize;
+
     ogg_reader.reader.end_of_file = false;
 
     ogg_reader.reader.read_at_offset(8196, 0).await?;
@@ -326,6 +502,13 @@ pub async fn remove_comments(meta: AudioFileMeta, names: Vec<String>) -> anyhow:
     ogg_reader.parse_header().await?;
     ogg_reader.parse_till_end().await?;
     ogg_reader.parse_header().await?;
+
+    if new_payload_len > old_capacity {
+        // the rewritten comments no longer fit in the pages the old ones
+        // occupied; repaginate from here instead of trying to force them in
+        return repaginate_comments(ogg_reader, vorbis_meta, &comment_bytes, kept_comments).await;
+    }
+
     ogg_reader.safe_skip(12 + vorbis_meta.vendor.len()).await?;
     ogg_reader
         .write_stream(&kept_comments.to_le_bytes())
@@ -334,11 +517,13 @@ pub async fn remove_comments(meta: AudioFileMeta, names: Vec<String>) -> anyhow:
     ogg_reader.write_stream(&comment_bytes).await.unwrap();
     ogg_reader
         .reader
-        .write_at_current_offset(vec![0; ogg_reader.segment_size - ogg_reader.cursor])
+        .stage_at_current_offset(vec![0; ogg_reader.segment_size - ogg_reader.cursor])
         .await
         .unwrap();
 
+    ogg_reader.reader.flush().await?;
     ogg_reader.recalculate_last_crc().await.unwrap();
+    ogg_reader.reader.flush().await?;
 
     let mut offset = vorbis_meta.end_ptr;
 
@@ -379,3 +564,59 @@ pub async fn remove_comments(meta: AudioFileMeta, names: Vec<String>) -> anyhow:
 
     Ok(())
 }
+
+/// Falls back to full repagination when the rewritten comment packet no
+/// longer fits in the pages the old one occupied: rebuilds the whole
+/// VORBIS_COMMENT packet (type marker, vendor, count, comments) across as
+/// many fresh pages as it takes, starting at the old comment page's
+/// physical slot, then copies the rest of the stream after it, renumbering
+/// every page that follows since the splice shifted their positions.
+async fn repaginate_comments<'a>(
+    ogg_reader: &mut OggPageReader<'a>,
+    vorbis_meta: &VorbisMeta,
+    comment_bytes: &[u8],
+    kept_comments: u32,
+) -> anyhow::Result<()> {
+    let header_ptr = ogg_reader.last_header_ptr as u64;
+    let marker_ptr = vorbis_meta.file_ptr as u64 - OPUS_TAGS_MARKER.len() as u64;
+    let marker = ogg_reader
+        .reader
+        .get_bytes_at(OPUS_TAGS_MARKER.len(), marker_ptr)
+        .await?;
+    let serial: [u8; 4] = ogg_reader
+        .reader
+        .get_bytes_at(4, header_ptr + 14)
+        .await?
+        .try_into()
+        .unwrap();
+    let first_page_number = ogg_reader.page_number;
+
+    let mut packet =
+        Vec::with_capacity(marker.len() + 4 + vorbis_meta.vendor.len() + 4 + comment_bytes.len());
+    packet.extend(marker);
+    packet.extend((vorbis_meta.vendor.len() as u32).to_le_bytes());
+    packet.extend(vorbis_meta.vendor.as_bytes());
+    packet.extend(kept_comments.to_le_bytes());
+    packet.extend(comment_bytes);
+
+    let (pages, page_count) = build_packet_pages(&serial, first_page_number, false, &packet);
+    let new_region_len = pages.len() as u64;
+
+    ogg_reader.reader.write_at(header_ptr, pages).await?;
+
+    let total_size = copy_and_renumber_pages(
+        &mut *ogg_reader.reader,
+        vorbis_meta.end_ptr as u64,
+        header_ptr + new_region_len,
+        first_page_number + page_count,
+    )
+    .await?;
+
+    unsafe {
+        let fd = ogg_reader.reader.file.as_raw_fd();
+        libc::ftruncate64(fd, total_size.try_into().unwrap());
+    }
+    ogg_reader.reader.file.sync_data().await.unwrap();
+
+    Ok(())
+}
@@ -0,0 +1,129 @@
+//! Async file I/O abstracted behind `FileBackend`, so the parsers don't
+//! hard-depend on `tokio_uring::fs::File` and therefore on a recent Linux
+//! kernel. Follows the same split pict-rs uses for its `io-uring` feature:
+//! io_uring is the default, fast path, and `tokio::fs` is the portable
+//! fallback used when the feature is off, so CI and non-Linux contributors
+//! can still exercise the FLAC/Ogg parsers.
+
+use std::{io, path::Path};
+
+/// The handful of positioned-I/O operations `UringBufReader` and the Ogg
+/// writer actually perform. Shaped like `tokio_uring::fs::File`'s
+/// completion-based API (the buffer is handed in and handed back alongside
+/// the result) so the io-uring backend is a zero-cost passthrough and both
+/// backends read the same at call sites.
+pub trait FileBackend: Sized {
+    async fn open(path: &Path) -> io::Result<Self>;
+    async fn read_at(&self, buf: Vec<u8>, offset: u64) -> (io::Result<usize>, Vec<u8>);
+    async fn read_exact_at(&self, buf: Vec<u8>, offset: u64) -> (io::Result<()>, Vec<u8>);
+    async fn write_all_at(&self, buf: Vec<u8>, offset: u64) -> (io::Result<()>, Vec<u8>);
+    async fn sync_all(&self) -> io::Result<()>;
+}
+
+#[cfg(feature = "io-uring")]
+mod uring_backend {
+    use super::FileBackend;
+    use std::{io, path::Path};
+    use tokio_uring::fs::{File, OpenOptions};
+
+    impl FileBackend for File {
+        async fn open(path: &Path) -> io::Result<Self> {
+            OpenOptions::new().read(true).write(true).open(path).await
+        }
+        async fn read_at(&self, buf: Vec<u8>, offset: u64) -> (io::Result<usize>, Vec<u8>) {
+            File::read_at(self, buf, offset).await
+        }
+        async fn read_exact_at(&self, buf: Vec<u8>, offset: u64) -> (io::Result<()>, Vec<u8>) {
+            File::read_exact_at(self, buf, offset).await
+        }
+        async fn write_all_at(&self, buf: Vec<u8>, offset: u64) -> (io::Result<()>, Vec<u8>) {
+            File::write_all_at(self, buf, offset).await
+        }
+        async fn sync_all(&self) -> io::Result<()> {
+            File::sync_all(self).await
+        }
+    }
+}
+
+#[cfg(feature = "io-uring")]
+pub type FileHandle = tokio_uring::fs::File;
+
+#[cfg(not(feature = "io-uring"))]
+mod tokio_backend {
+    use super::FileBackend;
+    use std::{
+        fs::File as StdFile,
+        io,
+        os::{
+            fd::{AsRawFd, RawFd},
+            unix::fs::FileExt,
+        },
+        path::{Path, PathBuf},
+        sync::Arc,
+    };
+
+    /// Fallback backend for platforms/CI without io_uring. `tokio::fs::File`
+    /// has no positioned `*_at` API, so reads/writes go through blocking
+    /// syscalls on a `spawn_blocking` thread instead.
+    pub struct TokioFile(Arc<StdFile>);
+
+    impl AsRawFd for TokioFile {
+        fn as_raw_fd(&self) -> RawFd {
+            self.0.as_raw_fd()
+        }
+    }
+
+    impl FileBackend for TokioFile {
+        async fn open(path: &Path) -> io::Result<Self> {
+            let path: PathBuf = path.to_owned();
+            let file = tokio::task::spawn_blocking(move || {
+                StdFile::options().read(true).write(true).open(path)
+            })
+            .await
+            .expect("blocking open task panicked")?;
+            Ok(Self(Arc::new(file)))
+        }
+
+        async fn read_at(&self, mut buf: Vec<u8>, offset: u64) -> (io::Result<usize>, Vec<u8>) {
+            let file = Arc::clone(&self.0);
+            tokio::task::spawn_blocking(move || {
+                let res = file.read_at(&mut buf, offset);
+                (res, buf)
+            })
+            .await
+            .expect("blocking read task panicked")
+        }
+
+        async fn read_exact_at(&self, mut buf: Vec<u8>, offset: u64) -> (io::Result<()>, Vec<u8>) {
+            let file = Arc::clone(&self.0);
+            // fill the whole capacity, as tokio_uring's read_exact_at does
+            buf.resize(buf.capacity(), 0);
+            tokio::task::spawn_blocking(move || {
+                let res = file.read_exact_at(&mut buf, offset);
+                (res, buf)
+            })
+            .await
+            .expect("blocking read task panicked")
+        }
+
+        async fn write_all_at(&self, buf: Vec<u8>, offset: u64) -> (io::Result<()>, Vec<u8>) {
+            let file = Arc::clone(&self.0);
+            tokio::task::spawn_blocking(move || {
+                let res = file.write_all_at(&buf, offset);
+                (res, buf)
+            })
+            .await
+            .expect("blocking write task panicked")
+        }
+
+        async fn sync_all(&self) -> io::Result<()> {
+            let file = Arc::clone(&self.0);
+            tokio::task::spawn_blocking(move || file.sync_all())
+                .await
+                .expect("blocking sync task panicked")
+        }
+    }
+}
+
+#[cfg(not(feature = "io-uring"))]
+pub use tokio_backend::TokioFile as FileHandle;
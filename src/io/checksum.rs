@@ -16,7 +16,15 @@ static CRC_TABLE: [u32; 256] = {
 };
 
 pub fn crc32(seq: &[u8]) -> u32 {
-    let mut crc: u32 = 0;
+    crc32_update(0, seq)
+}
+
+/// Feeds `seq` into an already-running CRC32, e.g. one seeded from a page's
+/// header so the checksum can be accumulated incrementally as segment bytes
+/// are written, instead of recomputing it from scratch over the whole page
+/// afterwards (mirrors the `ogg` crate's incremental `vorbis_crc32_update`).
+pub fn crc32_update(crc: u32, seq: &[u8]) -> u32 {
+    let mut crc = crc;
     for &b in seq {
         crc = (crc << 8) ^ CRC_TABLE[((crc >> 24) ^ (b as u32)) as usize]
     }
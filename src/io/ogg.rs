@@ -1,9 +1,10 @@
 use super::{
-    checksum::crc32,
-    reader::{Corruption, UringBufReader},
+    backend::FileBackend,
+    checksum::{crc32, crc32_update},
+    reader::{Corruption, OggError, UringBufReader},
 };
 use crate::formats::opus_ogg::OGG_MARKER;
-use std::{cmp::Ordering, mem};
+use std::{cmp::Ordering, collections::HashMap};
 
 pub struct OggPageReader<'a> {
     pub reader: &'a mut UringBufReader,
@@ -12,15 +13,64 @@ pub struct OggPageReader<'a> {
     pub segment_size: usize,
     pub last_header_ptr: usize,
     pub page_number: u32,
+    /// little-endian serial number of the logical bitstream the current
+    /// page belongs to (header offset 14)
+    pub serial: u32,
+    /// when set, pages whose serial doesn't match are transparently skipped
+    /// during `parse_header` instead of being exposed to `get_bytes`/
+    /// `parse_till_end`, so a caller reads a single logical bitstream
+    /// undisturbed by other streams multiplexed or chained into the same
+    /// physical file. Set via `select_stream`.
+    target_serial: Option<u32>,
+    /// last page number seen for each logical bitstream serial encountered
+    /// so far, regardless of `target_serial` — lets a caller discover which
+    /// streams are present in a chained/multiplexed file
+    pub streams: HashMap<u32, u32>,
+    /// true when the current page's header-type flags mark it as the first
+    /// page of a logical bitstream (0x02)
+    pub begin_of_stream: bool,
+    /// true when the current page's header-type flags mark it as the last
+    /// page of a logical bitstream (0x04)
+    pub end_of_stream: bool,
     last_header: Vec<u8>,
+    /// raw lacing/segment table of the current page, kept around (rather
+    /// than just summed into `segment_size`) so `next_packet` can tell where
+    /// one packet ends and the next begins within the page
+    segments: Vec<u8>,
+    /// index into `segments` of the next lacing value `next_packet` hasn't
+    /// consumed yet
+    segment_index: usize,
+    /// raw header-type flag byte of the current page (bit 0x01 = continued
+    /// packet, 0x02 = beginning of stream, 0x04 = end of stream)
+    header_type: u8,
+    /// every page's CRC32 is checked as it's parsed; normally (this field
+    /// false) a mismatch aborts parsing immediately with a `Corruption`. When
+    /// true, a mismatch is instead recorded in `checksum_mismatches` and
+    /// parsing continues, so a caller can survey an entire file for damage
+    /// rather than stopping at the first bad page.
+    verify_checksums: bool,
+    /// `(page_sequence_number, file_ptr, expected, actual)` for every page
+    /// whose stored CRC didn't match its recomputed one; only populated
+    /// when `verify_checksums` is on
+    pub checksum_mismatches: Vec<(u32, u64, u32, u32)>,
+    /// CRC32 accumulated incrementally as the current page is written,
+    /// seeded from `last_header` (CRC field zeroed) by `parse_header` and
+    /// fed each chunk `write_stream` stages. It covers only bytes staged by
+    /// `write_stream`, so it is the page's final checksum only when one
+    /// `write_stream` chunk fills the whole page body; `write_stream` then
+    /// writes it straight out, and otherwise flushes and reads the page back
+    /// through `recalculate_last_crc`.
+    running_crc: u32,
 }
 
 impl<'a> OggPageReader<'a> {
     pub fn header_length(&self) -> usize {
         self.last_header.len()
     }
-    /// Creates a new OggPageReader and immediately parses first header
-    /// returns Err if reader isn't positioned on header
+    /// Creates a new OggPageReader and immediately parses first header.
+    /// Returns Err if reader isn't positioned on header, or if the first
+    /// page's stored CRC32 doesn't match its recomputed one — every page is
+    /// checksummed as it's parsed, not just on write.
     pub async fn new(reader: &'a mut UringBufReader) -> Result<Self, Corruption> {
         let mut ogg_reader = OggPageReader {
             reader,
@@ -29,7 +79,48 @@ impl<'a> OggPageReader<'a> {
             segment_size: 0,
             ends_stream: true,
             page_number: 0,
+            serial: 0,
+            target_serial: None,
+            streams: HashMap::new(),
+            begin_of_stream: false,
+            end_of_stream: false,
             cursor: 0,
+            segments: Vec::new(),
+            segment_index: 0,
+            header_type: 0,
+            verify_checksums: false,
+            checksum_mismatches: Vec::new(),
+            running_crc: 0,
+        };
+        ogg_reader.parse_header().await?;
+        Ok(ogg_reader)
+    }
+    /// Like `new`, but doesn't abort at the first CRC32 mismatch: every bad
+    /// page is instead recorded in `checksum_mismatches` so a caller can
+    /// survey a whole file for damage (see `verify_ogg_checksums`) instead of
+    /// stopping at the first one found.
+    pub async fn new_with_integrity_check(
+        reader: &'a mut UringBufReader,
+    ) -> Result<Self, Corruption> {
+        let mut ogg_reader = OggPageReader {
+            reader,
+            last_header_ptr: 0,
+            last_header: Vec::with_capacity(64),
+            segment_size: 0,
+            ends_stream: true,
+            page_number: 0,
+            serial: 0,
+            target_serial: None,
+            streams: HashMap::new(),
+            begin_of_stream: false,
+            end_of_stream: false,
+            cursor: 0,
+            segments: Vec::new(),
+            segment_index: 0,
+            header_type: 0,
+            verify_checksums: true,
+            checksum_mismatches: Vec::new(),
+            running_crc: 0,
         };
         ogg_reader.parse_header().await?;
         Ok(ogg_reader)
@@ -43,14 +134,18 @@ impl<'a> OggPageReader<'a> {
                     .to_owned(),
                 path: self.reader.path.to_owned(),
                 file_cursor: self.reader.current_offset(),
+                cause: None,
             });
         }
         self.last_header_ptr = (self.reader.file_ptr + self.reader.cursor) as usize;
         let header_prefix = self.reader.get_bytes(27).await.map_err(|mut err| {
             err.message = "Not enough bytes for minimal Ogg Header".to_owned();
+            err.cause = Some(OggError::InvalidData);
             err
         })?;
 
+        let expected_crc = u32::from_le_bytes(header_prefix[22..26].try_into().unwrap());
+
         self.last_header.clear();
         self.last_header.extend(&header_prefix[0..22]);
         self.last_header.extend([0; 4]); // 0s out CRC
@@ -60,9 +155,22 @@ impl<'a> OggPageReader<'a> {
                 message: "OGG Marker was not found in the expected location.".to_owned(),
                 path: self.reader.path.to_owned(),
                 file_cursor: self.reader.current_offset(),
+                cause: Some(OggError::NoCapturePatternFound),
+            });
+        }
+        if header_prefix[4] != 0 {
+            return Err(Corruption {
+                message: format!(
+                    "Unsupported Ogg stream structure version: {}",
+                    header_prefix[4]
+                ),
+                path: self.reader.path.to_owned(),
+                file_cursor: self.reader.current_offset(),
+                cause: Some(OggError::InvalidStreamStructVer(header_prefix[4])),
             });
         }
         let header: usize = header_prefix[5].into();
+        let serial = u32::from_le_bytes(header_prefix[14..18].try_into().unwrap());
         let page_number = u32::from_be_bytes(header_prefix[18..22].try_into().unwrap());
         let segment_len: usize = header_prefix[26].into();
         let segments = self
@@ -71,18 +179,96 @@ impl<'a> OggPageReader<'a> {
             .await
             .map_err(|mut err| {
                 err.message = "Not enough bytes for header segments".to_owned();
+                err.cause = Some(OggError::InvalidData);
                 err
             })?;
         let segment_total = segments.iter().fold(0, |acc, x| acc + *x as usize);
+        self.segments.clear();
+        self.segments.extend_from_slice(segments);
+        self.segment_index = 0;
         self.last_header.extend(segments);
+        self.running_crc = crc32(&self.last_header);
         self.segment_size = segment_total;
         self.page_number = page_number;
+        self.serial = serial;
+        self.streams.insert(serial, page_number);
+        self.header_type = header as u8;
+        self.begin_of_stream = self.header_type & 0x02 != 0;
+        self.end_of_stream = self.header_type & 0x04 != 0;
         self.ends_stream = header > 4 || segment_total % 255 > 0;
         self.cursor = 0;
+
+        self.verify_page_crc(expected_crc).await?;
+
+        if let Some(target) = self.target_serial {
+            if serial != target {
+                self.reader.skip(self.segment_size as u64).await?;
+                self.cursor = self.segment_size;
+                return Box::pin(self.parse_header()).await;
+            }
+        }
+
         Ok(())
     }
+    /// Restricts subsequent reads to the logical bitstream identified by
+    /// `serial`: any other stream's pages are transparently skipped by
+    /// `parse_header` instead of being handed to `get_bytes`/`parse_till_end`.
+    /// Safe to call right away even if the page already loaded belongs to a
+    /// different stream — it skips straight past it.
+    pub async fn select_stream(&mut self, serial: u32) -> Result<(), Corruption> {
+        self.target_serial = Some(serial);
+        if self.serial != serial {
+            self.reader.skip(self.page_left() as u64).await?;
+            self.cursor = self.segment_size;
+            self.parse_header().await?;
+        }
+        Ok(())
+    }
+    /// Reads this page's body and feeds `last_header` (header with the CRC
+    /// field already zeroed) plus the body into `crc32`, the same byte
+    /// layout `write_last_crc`/`recalculate_last_crc` build when writing a
+    /// page's checksum. Compares against `expected`, the CRC stored in the
+    /// header before it was zeroed. On mismatch, either records it in
+    /// `checksum_mismatches` (when `verify_checksums` is on) or aborts with a
+    /// `Corruption` carrying both values and `last_header_ptr`.
+    async fn verify_page_crc(&mut self, expected: u32) -> Result<(), Corruption> {
+        let body_ptr = self.last_header_ptr as u64 + self.last_header.len() as u64;
+        let body = self
+            .reader
+            .get_bytes_at(self.segment_size, body_ptr)
+            .await?;
+        let mut page = self.last_header.clone();
+        page.extend(&body);
+        let actual = crc32(&page);
+        if actual == expected {
+            return Ok(());
+        }
+        if self.verify_checksums {
+            self.checksum_mismatches.push((
+                self.page_number,
+                self.last_header_ptr as u64,
+                expected,
+                actual,
+            ));
+            return Ok(());
+        }
+        Err(Corruption {
+            path: self.reader.path.to_owned(),
+            file_cursor: self.last_header_ptr as u64,
+            message: format!(
+                "Ogg page CRC32 mismatch at header {}: stored {expected:#010x}, computed {actual:#010x}",
+                self.last_header_ptr
+            ),
+            cause: Some(OggError::HashMismatch {
+                expected,
+                calculated: actual,
+            }),
+        })
+    }
     /// Gets usize amount of bytes, automatically skipping headers.
-    /// Ignores streams, can return bytes from different streams
+    /// Ignores streams and can return bytes from different streams, unless
+    /// `select_stream` has restricted this reader to a single serial, in
+    /// which case other streams' pages are invisibly skipped instead.
     pub async fn get_bytes(&mut self, size: usize) -> Result<Vec<u8>, Corruption> {
         let mut result = Vec::with_capacity(size);
         let mut size_left = size;
@@ -115,6 +301,7 @@ impl<'a> OggPageReader<'a> {
                 message: "Attempted to read data from header bytes (mismatched pages)".to_owned(),
                 path: self.reader.path.to_owned(),
                 file_cursor: self.reader.current_offset(),
+                cause: Some(OggError::InvalidData),
             }),
             _ => Ok(()),
         }
@@ -133,6 +320,61 @@ impl<'a> OggPageReader<'a> {
         Ok(result)
     }
 
+    /// Reconstructs one packet by concatenating lacing-table segments until a
+    /// segment shorter than 255 bytes terminates it — a run of 255-valued
+    /// segments means the packet continues, possibly into the next page.
+    /// Honors the header-type continuation flag (bit 0x01) so a packet split
+    /// across a page boundary is rejoined rather than treated as a fresh one,
+    /// erroring out if a page's continuation flag disagrees with whether a
+    /// packet was actually left open. Returns `Ok(None)` once the page
+    /// carrying the stream's end-of-stream flag has yielded its last packet.
+    pub async fn next_packet(&mut self) -> Result<Option<Vec<u8>>, Corruption> {
+        if self.segment_index >= self.segments.len() {
+            if self.end_of_stream {
+                return Ok(None);
+            }
+            self.parse_header().await?;
+            if self.header_type & 0x01 != 0 {
+                return Err(Corruption {
+                    message: "Page marked as a packet continuation, but no packet was left open"
+                        .to_owned(),
+                    path: self.reader.path.to_owned(),
+                    file_cursor: self.last_header_ptr as u64,
+                    cause: Some(OggError::InvalidData),
+                });
+            }
+        }
+
+        let mut packet = Vec::new();
+        loop {
+            if self.segment_index >= self.segments.len() {
+                if self.end_of_stream {
+                    break;
+                }
+                self.parse_header().await?;
+                if self.header_type & 0x01 == 0 {
+                    return Err(Corruption {
+                        message: "Packet continues past a page boundary, but the next page \
+                                  didn't mark itself as a continuation"
+                            .to_owned(),
+                        path: self.reader.path.to_owned(),
+                        file_cursor: self.last_header_ptr as u64,
+                        cause: Some(OggError::InvalidData),
+                    });
+                }
+                continue;
+            }
+            let seg_len = self.segments[self.segment_index] as usize;
+            self.segment_index += 1;
+            packet.extend(self.reader.get_bytes(seg_len).await?);
+            self.cursor += seg_len;
+            if seg_len < 255 {
+                break;
+            }
+        }
+        Ok(Some(packet))
+    }
+
     #[inline(always)]
     pub const fn page_left(&self) -> usize {
         self.segment_size - self.cursor
@@ -159,22 +401,21 @@ impl<'a> OggPageReader<'a> {
 }
 
 impl<'a> OggPageReader<'a> {
-    async fn write_last_crc(&mut self, segment_bytes: &[u8]) -> Result<(), Corruption> {
-        let (res, _buf) = self
-            .reader
-            .file
-            .write_all_at(
-                crc32(segment_bytes).to_le_bytes().to_vec(),
-                self.last_header_ptr as u64 + 22, // crc offset
-            )
-            .await;
-        res.map_err(|err| Corruption {
-            path: self.reader.path.to_owned(),
-            file_cursor: self.last_header_ptr as u64 + 22,
-            message: format!("Failed to write CRC32. IO error: {err:?}"),
-        })
+    /// Stages the CRC32 write instead of submitting it right away, so a
+    /// caller rewriting many pages (e.g. `rehash_headers`) can flush them
+    /// all together (see `UringBufReader::stage_write`/`flush`).
+    async fn write_last_crc(&mut self, crc: u32) -> Result<(), Corruption> {
+        self.reader.stage_write(
+            self.last_header_ptr as u64 + 22, // crc offset
+            crc.to_le_bytes().to_vec(),
+        );
+        Ok(())
     }
-    /// reads entire page (from last header) including header and recalculates its checksum
+    /// Reads the entire page back (from last header) and recomputes its
+    /// checksum from scratch. Reads what is on disk, so staged writes to
+    /// the page have to be flushed first. Used for rehashing a page that
+    /// wasn't written through `write_stream`, and by `write_stream` for a
+    /// page it filled only in part (see `running_crc`).
     pub async fn recalculate_last_crc(&mut self) -> Result<(), Corruption> {
         let full_page_size = self.segment_size + self.last_header.len();
         let buf = Vec::with_capacity(full_page_size);
@@ -196,13 +437,16 @@ impl<'a> OggPageReader<'a> {
             let ptr = buf.as_mut_ptr();
             std::ptr::copy_nonoverlapping([0; 4].as_ptr(), ptr.add(22), 4);
         }
-        let res = self.write_last_crc(&buf).await;
+        let crc = crc32(&buf);
         drop(buf);
-        res
+        self.write_last_crc(crc).await
     }
 
-    /// Writes buffer into segment part of stream at current cursor
-    /// recalculates checksum
+    /// Writes buffer into segment part of stream at current cursor,
+    /// maintaining `running_crc` incrementally. When a page fills up in a
+    /// single chunk its checksum is written straight out without a
+    /// read-back; a page that also holds bytes not written here is flushed
+    /// and rehashed from disk.
     pub async fn write_stream(&mut self, buf: &[u8]) -> Result<(), Corruption> {
         self.check_cursor().await?;
 
@@ -215,17 +459,16 @@ impl<'a> OggPageReader<'a> {
 
         let chunk_len = current_chunk.len();
         self.reader
-            .write_at_current_offset(current_chunk.to_vec())
+            .stage_at_current_offset(current_chunk.to_vec())
             .await?;
         self.cursor += chunk_len;
+        self.running_crc = crc32_update(self.running_crc, current_chunk);
 
         if self.cursor == self.segment_size {
             if chunk_len == self.segment_size {
-                let mut header = mem::take(&mut self.last_header);
-                header.extend(current_chunk);
-                self.write_last_crc(&header).await?;
-                drop(header);
+                self.write_last_crc(self.running_crc).await?;
             } else {
+                self.reader.flush().await?;
                 self.recalculate_last_crc().await?;
             }
             self.parse_header().await?;
@@ -245,7 +488,7 @@ impl<'a> OggPageReader<'a> {
             self.write_stream(&vec![0; remaining_segment]).await?;
         }
         self.write_stream(&vec![0; self.page_left()]).await?;
-        Ok(())
+        self.reader.flush().await
     }
     pub async fn rehash_headers(&mut self) -> Result<(), Corruption> {
         while !self.ends_stream {
@@ -253,6 +496,117 @@ impl<'a> OggPageReader<'a> {
             self.recalculate_last_crc().await?;
             self.check_cursor().await?;
         }
-        Ok(())
+        self.reader.flush().await
+    }
+}
+
+// segments of 255 bytes, continuation page, no terminating (< 255) lacing value needed
+const CONTINUATION_PAYLOAD: usize = 255 * 255;
+// leaves one segment free for the terminating lacing value, even when data lands on a 255 boundary
+const FINAL_PAGE_MAX_PAYLOAD: usize = 254 * 255 + 254;
+
+/// Builds as many physical pages as it takes to carry a single logical
+/// packet that's too big for one page: continuation pages filled with
+/// solid runs of 255-byte segments, and a final page carrying whatever's
+/// left plus its terminating lacing value (an empty trailing page, holding
+/// only a 0 lacing value, when `data`'s length is a nonzero multiple of
+/// 255 * 255). `first_continues`
+/// marks whether `data` itself continues a packet that was already open on
+/// an earlier page, rather than starting a fresh one here.
+/// Returns the serialized pages (CRC already filled in) and how many there are.
+pub fn build_packet_pages(
+    serial: &[u8; 4],
+    first_page_number: u32,
+    first_continues: bool,
+    data: &[u8],
+) -> (Vec<u8>, u32) {
+    let mut pages = Vec::new();
+    let mut page_number = first_page_number;
+    let mut continues = first_continues;
+    let mut remaining = data;
+
+    while remaining.len() > FINAL_PAGE_MAX_PAYLOAD {
+        let (chunk, rest) = remaining.split_at(CONTINUATION_PAYLOAD);
+        let lacing = vec![255u8; chunk.len() / 255];
+        pages.extend(build_page(serial, page_number, continues, &lacing, chunk));
+        page_number += 1;
+        continues = true;
+        remaining = rest;
+    }
+
+    let mut lacing = vec![255u8; remaining.len() / 255];
+    lacing.push((remaining.len() % 255) as u8);
+    pages.extend(build_page(
+        serial,
+        page_number,
+        continues,
+        &lacing,
+        remaining,
+    ));
+    page_number += 1;
+
+    (pages, page_number - first_page_number)
+}
+
+fn build_page(
+    serial: &[u8; 4],
+    page_number: u32,
+    continues: bool,
+    lacing: &[u8],
+    data: &[u8],
+) -> Vec<u8> {
+    let mut page = Vec::with_capacity(27 + lacing.len() + data.len());
+    page.extend(OGG_MARKER);
+    page.push(0); // stream structure version
+    page.push(if continues { 0x01 } else { 0x00 });
+    page.extend([0u8; 8]); // granule position: no samples decoded from header packets
+    page.extend(serial);
+    page.extend(page_number.to_be_bytes());
+    page.extend([0u8; 4]); // CRC placeholder, patched in below
+    page.push(lacing.len() as u8);
+    page.extend(lacing);
+    page.extend(data);
+
+    let crc = crc32(&page).to_le_bytes();
+    page[22..26].copy_from_slice(&crc);
+    page
+}
+
+/// Copies every remaining physical page from `src_offset` through EOF over
+/// to `dst_offset`, renumbering them sequentially from `next_page_number`
+/// and recalculating each page's CRC to match. Needed after splicing extra
+/// pages in earlier in the stream, since every page downstream of the
+/// splice shifts position and its sequence number needs to stay monotonic.
+pub async fn copy_and_renumber_pages(
+    reader: &mut UringBufReader,
+    mut src_offset: u64,
+    mut dst_offset: u64,
+    mut next_page_number: u32,
+) -> Result<u64, Corruption> {
+    loop {
+        let Ok(header_prefix) = reader.get_bytes_at(27, src_offset).await else {
+            break;
+        };
+        let segment_len = header_prefix[26] as usize;
+        let segment_total: usize = reader
+            .get_bytes_at(segment_len, src_offset + 27)
+            .await?
+            .iter()
+            .map(|&b| b as usize)
+            .sum();
+        let page_len = 27 + segment_len + segment_total;
+
+        let mut page = reader.get_bytes_at(page_len, src_offset).await?;
+        page[22..26].copy_from_slice(&[0; 4]);
+        page[18..22].copy_from_slice(&next_page_number.to_be_bytes());
+        let crc = crc32(&page).to_le_bytes();
+        page[22..26].copy_from_slice(&crc);
+
+        reader.write_at(dst_offset, page).await?;
+
+        src_offset += page_len as u64;
+        dst_offset += page_len as u64;
+        next_page_number += 1;
     }
+    Ok(dst_offset)
 }
@@ -1,23 +1,53 @@
+use super::backend::{FileBackend, FileHandle};
 use crate::{
-    db::vorbis::FLAC_MARKER,
+    db::{audio_file::AudioFile, vorbis::FLAC_MARKER},
     formats::{
-        flac::parse_flac,
-        opus_ogg::{parse_ogg_pages, OGG_MARKER},
+        audio_hash::hash_audio_payload,
+        container::{Flac, Id3v2, OggOpus, TagContainer},
+        id3::ID3_MARKER,
+        opus_ogg::OGG_MARKER,
     },
     queue::TaskQueue,
 };
+use futures::future;
 use ignore::{WalkBuilder, WalkState};
+use sqlx::SqlitePool;
 use std::{cmp::min, sync::Mutex};
 use std::{
+    collections::BTreeMap,
     io::{self},
-    path::PathBuf,
+    mem,
+    ops::Range,
+    path::{Path, PathBuf},
     sync::Arc,
+    time::UNIX_EPOCH,
 };
 use tokio::sync::Semaphore;
-use tokio_uring::fs::File;
 
 const BASE_SIZE: usize = 8196;
 
+/// Default per-reader cap on a single length-driven read (see
+/// `UringBufReader::check_declared_len`). 64 MiB comfortably covers any
+/// legitimate comment/picture/STREAMINFO block while still refusing to
+/// allocate on behalf of a file lying about a multi-GB field length.
+const DEFAULT_MAX_ALLOC: usize = 64 * 1024 * 1024;
+
+/// Allocates a zeroed buffer via a fallible reservation, so a size that's
+/// within bounds but still too large for available memory surfaces as a
+/// `Corruption` instead of aborting the process the way `vec![0; size]`'s
+/// infallible allocator would.
+fn try_zeroed_vec(path: &Path, cursor: u64, size: usize) -> Result<Vec<u8>, Corruption> {
+    let mut buf = Vec::new();
+    buf.try_reserve_exact(size).map_err(|_| Corruption {
+        message: format!("failed to reserve {size} bytes"),
+        file_cursor: cursor,
+        path: path.to_owned(),
+        cause: None,
+    })?;
+    buf.resize(size, 0);
+    Ok(buf)
+}
+
 pub struct ThrottleConfig {
     max_concurrent_tasks: usize,
 }
@@ -30,11 +60,35 @@ impl ThrottleConfig {
     }
 }
 
+/// What kind of Ogg spec violation caused a `Corruption`, for callers that
+/// need to branch on the failure instead of matching free text. Mirrors the
+/// `ogg` crate's `OggReadError`. `None` on `Corruption.cause` covers every
+/// other kind of failure (plain I/O errors, FLAC/Vorbis block corruption,
+/// programming-error assertions), which this crate still only reports as a
+/// message.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum OggError {
+    /// the 4-byte "OggS" capture pattern wasn't found where a page header
+    /// was expected
+    NoCapturePatternFound,
+    /// the stream structure version byte (header offset 4) wasn't 0, the
+    /// only version this crate knows how to parse
+    InvalidStreamStructVer(u8),
+    /// a page's stored CRC32 didn't match the one recomputed while reading it
+    HashMismatch { expected: u32, calculated: u32 },
+    /// the page or packet structure didn't make sense (truncated header,
+    /// mismatched continuation flags, etc.)
+    InvalidData,
+}
+
 #[derive(Debug)]
 pub struct Corruption {
     pub path: PathBuf,
     pub message: String,
     pub file_cursor: u64,
+    /// typed cause for Ogg-specific failures; `None` for everything else
+    /// (see `OggError`)
+    pub cause: Option<OggError>,
 }
 
 impl Corruption {
@@ -43,7 +97,100 @@ impl Corruption {
             file_cursor,
             path,
             message: format!("IO Error: {io_error:?}"),
+            cause: None,
+        }
+    }
+}
+
+/// Tracks which `[start, end)` byte ranges of the file are currently
+/// resident in memory, independent of the sequential `buf`/`cursor` used by
+/// `read_next`/`get_bytes`. Backs `fetch`/`fetch_blocking`/`get_range`, so a
+/// reader jumping between distant regions (e.g. a comment block and an
+/// embedded picture block) doesn't have to discard and re-read `buf`.
+#[derive(Debug, Default)]
+struct RangeCache {
+    /// resident ranges keyed by start offset, kept sorted (by `BTreeMap`
+    /// iteration order) and merged so no two entries overlap or touch
+    ranges: BTreeMap<u64, Vec<u8>>,
+}
+
+impl RangeCache {
+    fn end_of(start: u64, buf: &[u8]) -> u64 {
+        start + buf.len() as u64
+    }
+
+    /// every sub-range of `range` that isn't already resident, in order
+    fn missing(&self, range: &Range<u64>) -> Vec<Range<u64>> {
+        let mut gaps = Vec::new();
+        let mut cursor = range.start;
+        for (&start, buf) in &self.ranges {
+            let end = Self::end_of(start, buf);
+            if end <= cursor {
+                continue;
+            }
+            if start >= range.end {
+                break;
+            }
+            if start > cursor {
+                gaps.push(cursor..start);
+            }
+            cursor = cursor.max(end);
+            if cursor >= range.end {
+                break;
+            }
+        }
+        if cursor < range.end {
+            gaps.push(cursor..range.end);
+        }
+        gaps
+    }
+
+    /// inserts a freshly-read chunk, merging it with any resident range it
+    /// overlaps or touches so `ranges` stays non-overlapping
+    fn insert(&mut self, start: u64, buf: Vec<u8>) {
+        let mut merged_start = start;
+        let mut merged_end = Self::end_of(start, &buf);
+
+        let touching: Vec<u64> = self
+            .ranges
+            .iter()
+            .filter(|(&existing_start, existing_buf)| {
+                let existing_end = Self::end_of(existing_start, existing_buf);
+                existing_start <= merged_end && existing_end >= merged_start
+            })
+            .map(|(&existing_start, _)| existing_start)
+            .collect();
+
+        let mut removed = Vec::with_capacity(touching.len());
+        for existing_start in touching {
+            let existing_buf = self.ranges.remove(&existing_start).unwrap();
+            merged_start = merged_start.min(existing_start);
+            merged_end = merged_end.max(Self::end_of(existing_start, &existing_buf));
+            removed.push((existing_start, existing_buf));
         }
+
+        let mut merged = vec![0u8; (merged_end - merged_start) as usize];
+        for (existing_start, existing_buf) in removed {
+            let offset = (existing_start - merged_start) as usize;
+            merged[offset..offset + existing_buf.len()].copy_from_slice(&existing_buf);
+        }
+        let offset = (start - merged_start) as usize;
+        merged[offset..offset + buf.len()].copy_from_slice(&buf);
+
+        self.ranges.insert(merged_start, merged);
+    }
+
+    /// the bytes covering `range`; only meaningful once `covers(range)` is true
+    fn slice(&self, range: &Range<u64>) -> Vec<u8> {
+        for (&start, buf) in &self.ranges {
+            let end = Self::end_of(start, buf);
+            if start <= range.start && range.end <= end {
+                let from = (range.start - start) as usize;
+                let to = (range.end - start) as usize;
+                return buf[from..to].to_vec();
+            }
+        }
+        Vec::new()
     }
 }
 
@@ -53,7 +200,22 @@ pub struct UringBufReader {
     pub cursor: u64,
     pub file_ptr: u64,
     pub end_of_file: bool,
-    pub file: File,
+    pub file: Arc<FileHandle>,
+    /// writes staged by `stage_write`/`stage_at_current_offset`, waiting on
+    /// a `flush()` to be submitted. Lets a writer doing many small writes
+    /// (e.g. rehashing every page's CRC) submit them together instead of
+    /// one io_uring round trip apiece.
+    pending_writes: Vec<(u64, Vec<u8>)>,
+    /// ranges fetched via `fetch`/`fetch_blocking`, shared with the
+    /// fire-and-forget prefetch tasks `fetch` spawns
+    range_cache: Arc<tokio::sync::Mutex<RangeCache>>,
+    /// upper bound on a single length-driven read (see `check_declared_len`);
+    /// defaults to `DEFAULT_MAX_ALLOC`, overridable via `with_max_alloc`
+    max_alloc: usize,
+    /// total file size, if known (see `with_file_size`), used to reject a
+    /// length-driven read (e.g. a comment/picture block whose declared size
+    /// exceeds what's actually left in the file) before it ever allocates
+    pub(crate) file_size: Option<u64>,
 }
 
 impl UringBufReader {
@@ -65,6 +227,55 @@ impl UringBufReader {
         self.skip_read(buf_len, 0).await?;
         res.map_err(|err| Corruption::io(self.path.to_owned(), self.current_offset(), err))
     }
+
+    /// writes buf at an arbitrary file offset, without touching cursor/buf state
+    pub async fn write_at(&mut self, offset: u64, buf: Vec<u8>) -> Result<(), Corruption> {
+        let (res, buf) = self.file.write_all_at(buf, offset).await;
+        drop(buf);
+        res.map_err(|err| Corruption::io(self.path.to_owned(), offset, err))
+    }
+
+    /// reads size bytes at an arbitrary file offset, without touching cursor/buf state
+    pub async fn get_bytes_at(&mut self, size: usize, offset: u64) -> Result<Vec<u8>, Corruption> {
+        self.check_alloc_cap(size, offset)?;
+        let buf = try_zeroed_vec(&self.path, offset, size)?;
+        let (res, buf) = self.file.read_exact_at(buf, offset).await;
+        res.map_err(|err| Corruption::io(self.path.to_owned(), offset, err))?;
+        Ok(buf)
+    }
+
+    /// Stages buf to be written at an arbitrary file offset instead of
+    /// submitting it right away; pairs with `flush` to batch many small
+    /// writes (CRCs, individual segments) into one submission round.
+    pub fn stage_write(&mut self, offset: u64, buf: Vec<u8>) {
+        self.pending_writes.push((offset, buf));
+    }
+
+    /// Like `write_at_current_offset`, but stages the write for `flush`
+    /// instead of submitting it immediately. Cursor/buf bookkeeping still
+    /// happens synchronously, since that's tracking reader state, not
+    /// waiting on the write itself.
+    pub async fn stage_at_current_offset(&mut self, buf: Vec<u8>) -> Result<(), Corruption> {
+        let buf_len = buf.len() as u64;
+        self.stage_write(self.current_offset(), buf);
+        self.skip_read(buf_len, 0).await
+    }
+
+    /// Submits every write staged via `stage_write`/`stage_at_current_offset`
+    /// concurrently, so io_uring can batch them into a single submission
+    /// round instead of one round trip per write. A no-op if nothing is
+    /// pending.
+    pub async fn flush(&mut self) -> Result<(), Corruption> {
+        let writes = mem::take(&mut self.pending_writes);
+        let path = &self.path;
+        let file = &self.file;
+        future::try_join_all(writes.into_iter().map(|(offset, buf)| async move {
+            let (res, _buf) = file.write_all_at(buf, offset).await;
+            res.map_err(|err| Corruption::io(path.to_owned(), offset, err))
+        }))
+        .await?;
+        Ok(())
+    }
 }
 
 impl UringBufReader {
@@ -72,16 +283,130 @@ impl UringBufReader {
     pub const fn current_offset(&self) -> u64 {
         self.file_ptr + self.cursor
     }
-    pub fn new(file: File, path: PathBuf) -> Self {
+    pub fn new(file: FileHandle, path: PathBuf) -> Self {
         UringBufReader {
             buf: Vec::new(),
-            file,
+            file: Arc::new(file),
             path,
             end_of_file: false,
             cursor: 0u64,
             file_ptr: 0u64,
+            pending_writes: Vec::new(),
+            range_cache: Arc::new(tokio::sync::Mutex::new(RangeCache::default())),
+            max_alloc: DEFAULT_MAX_ALLOC,
+            file_size: None,
         }
     }
+
+    /// Records the file's total size, so later length-driven reads can be
+    /// rejected once they'd run past the end of the file instead of
+    /// attempting whatever allocation a corrupt length field asks for.
+    pub fn with_file_size(mut self, file_size: u64) -> Self {
+        self.file_size = Some(file_size);
+        self
+    }
+
+    /// Overrides the default per-reader allocation cap (see `DEFAULT_MAX_ALLOC`).
+    pub fn with_max_alloc(mut self, max_alloc: usize) -> Self {
+        self.max_alloc = max_alloc;
+        self
+    }
+
+    /// Guards a raw allocation size against the configurable per-reader cap.
+    /// Used by the low-level read paths (`extend_buf`, `read_at_offset`,
+    /// `get_bytes_at`), whose requested size is usually padded with look-
+    /// ahead slack rather than an exact declared length, so it can't also be
+    /// checked against bytes remaining in the file without rejecting
+    /// legitimate reads near the end of a small file.
+    fn check_alloc_cap(&self, size: usize, offset: u64) -> Result<(), Corruption> {
+        if size > self.max_alloc {
+            return Err(Corruption {
+                message: format!(
+                    "refusing to allocate {size} bytes: exceeds the {}-byte per-reader cap",
+                    self.max_alloc
+                ),
+                file_cursor: offset,
+                path: self.path.to_owned(),
+                cause: None,
+            });
+        }
+        Ok(())
+    }
+
+    /// Guards a length-driven read (a comment/picture/block size taken
+    /// straight from the file, e.g. `mime_len`/`picture_len`/`block_length`)
+    /// against both the per-reader cap and, when the file's total size is
+    /// known, whatever's actually left to read — so a malformed file can't
+    /// turn a single bad length field into a multi-GB allocation attempt.
+    pub(crate) fn check_declared_len(&self, amount: usize, offset: u64) -> Result<(), Corruption> {
+        self.check_alloc_cap(amount, offset)?;
+        if let Some(file_size) = self.file_size {
+            let remaining = file_size.saturating_sub(offset);
+            if amount as u64 > remaining {
+                return Err(Corruption {
+                    message: format!(
+                        "refusing to read {amount} bytes: only {remaining} bytes remain in the file"
+                    ),
+                    file_cursor: offset,
+                    path: self.path.to_owned(),
+                    cause: None,
+                });
+            }
+        }
+        Ok(())
+    }
+
+    /// Issues reads for whatever part of `range` isn't already resident,
+    /// without waiting for them to land, so read-ahead (e.g. the next Ogg
+    /// page) can run while the caller keeps working with what it already
+    /// has. Pick the result up later with `fetch_blocking`/`get_range`.
+    pub async fn fetch(&self, range: Range<u64>) {
+        let gaps = {
+            let cache = self.range_cache.lock().await;
+            cache.missing(&range)
+        };
+        for gap in gaps {
+            let file = Arc::clone(&self.file);
+            let cache = Arc::clone(&self.range_cache);
+            let start = gap.start;
+            let size = (gap.end - gap.start) as usize;
+            let task = async move {
+                let (res, buf) = file.read_at(vec![0; size], start).await;
+                if res.is_ok() {
+                    cache.lock().await.insert(start, buf);
+                }
+            };
+            #[cfg(feature = "io-uring")]
+            tokio_uring::spawn(task);
+            #[cfg(not(feature = "io-uring"))]
+            tokio::spawn(task);
+        }
+    }
+
+    /// Like `fetch`, but awaits every gap read so `range` is guaranteed
+    /// resident once this returns.
+    pub async fn fetch_blocking(&self, range: Range<u64>) -> Result<(), Corruption> {
+        let gaps = {
+            let cache = self.range_cache.lock().await;
+            cache.missing(&range)
+        };
+        for gap in gaps {
+            let size = (gap.end - gap.start) as usize;
+            let (res, buf) = self.file.read_at(vec![0; size], gap.start).await;
+            res.map_err(|err| Corruption::io(self.path.to_owned(), gap.start, err))?;
+            self.range_cache.lock().await.insert(gap.start, buf);
+        }
+        Ok(())
+    }
+
+    /// Serves `range` from the resident range cache, fetching whatever's
+    /// missing first. Independent of `buf`/`cursor`, so seeking between
+    /// distant regions (a comment block, a far-off embedded picture block)
+    /// doesn't discard and re-read the sequential buffer.
+    pub async fn get_range(&self, range: Range<u64>) -> Result<Vec<u8>, Corruption> {
+        self.fetch_blocking(range.clone()).await?;
+        Ok(self.range_cache.lock().await.slice(&range))
+    }
     /// skips u64 bytes, then allocates usize bytes if needed
     /// if cursor is at EOF, returns io::Error instead of allocating
     pub async fn skip_read(&mut self, skip: u64, size: usize) -> Result<(), Corruption> {
@@ -90,6 +415,7 @@ impl UringBufReader {
             if self.end_of_file {
                 return Err(Corruption {
                     message: "Reached end of file".to_owned(),
+                    cause: None,
                     path: self.path.to_owned(),
                     file_cursor: self.current_offset(),
                 });
@@ -113,8 +439,9 @@ impl UringBufReader {
     /// in case you don't want to replace the current buf
     /// sets cursor to 0 and file_ptr to offset
     pub async fn read_at_offset(&mut self, size: usize, offset: u64) -> Result<usize, Corruption> {
+        self.check_alloc_cap(size, offset)?;
         self.buf.clear();
-        let buf = vec![0; size];
+        let buf = try_zeroed_vec(&self.path, offset, size)?;
         self.cursor = 0;
         self.file_ptr = offset;
         let (res, mut _buf) = self.file.read_at(buf, offset).await;
@@ -133,10 +460,12 @@ impl UringBufReader {
             return Err(Corruption {
                 path: self.path.to_owned(),
                 message: "Reached end of file".to_owned(),
+                cause: None,
                 file_cursor: self.current_offset(),
             });
         }
-        let buf = vec![0; size];
+        self.check_alloc_cap(size, self.current_offset())?;
+        let buf = try_zeroed_vec(&self.path, self.current_offset(), size)?;
         let (res, mut _buf) = self
             .file
             .read_at(buf, self.file_ptr + self.buf.len() as u64)
@@ -160,6 +489,7 @@ impl UringBufReader {
     /// extends by missing amount + additional 8196 bytes
     /// returns rest of the buffer if it reaches EOF
     pub async fn get_bytes(&mut self, amount: usize) -> Result<&[u8], Corruption> {
+        self.check_declared_len(amount, self.current_offset())?;
         let buf_len = self.buf.len();
         if buf_len <= amount + self.cursor as usize {
             self.extend_buf(amount + self.cursor as usize - buf_len + BASE_SIZE)
@@ -168,6 +498,7 @@ impl UringBufReader {
                 return Err(Corruption {
                     file_cursor: self.current_offset(),
                     message: format!("File ended before {amount} bytes could be read"),
+                    cause: None,
                     path: self.path.to_owned(),
                 });
             }
@@ -185,6 +516,7 @@ impl UringBufReader {
     /// returns rest of the buffer if it reaches EOF
     /// returns part of the buf if EOF is reached before reading full amount
     pub async fn get_bytes_unchecked(&mut self, amount: usize) -> Result<&[u8], Corruption> {
+        self.check_declared_len(amount, self.current_offset())?;
         let buf_len = self.buf.len();
         if buf_len <= amount + self.cursor as usize {
             self.extend_buf(amount + self.cursor as usize - buf_len + BASE_SIZE)
@@ -210,6 +542,7 @@ impl UringBufReader {
             return Err(Corruption {
                 path: self.path.to_owned(),
                 message: "File ended".to_owned(),
+                cause: None,
                 file_cursor: self.current_offset(),
             });
         }
@@ -246,7 +579,8 @@ pub fn walk_dir(path: &str) -> Vec<PathBuf> {
         .collect::<Vec<PathBuf>>()
 }
 
-/// requires io_uring runtime
+/// requires an io_uring runtime when the `io-uring` feature is on; falls
+/// back to the regular tokio runtime otherwise (see `FileBackend`)
 pub async fn load_data_from_paths(paths: Vec<PathBuf>, config: ThrottleConfig) {
     let mut tasks = Vec::new();
     let semaphore = Arc::new(Semaphore::new(config.max_concurrent_tasks));
@@ -254,11 +588,15 @@ pub async fn load_data_from_paths(paths: Vec<PathBuf>, config: ThrottleConfig) {
     for path in paths {
         let semaphore = Arc::clone(&semaphore);
         let queue = Arc::clone(&queue);
-        let spawn = tokio_uring::spawn(async move {
+        let task = async move {
             // just dont close semaphore and it will be all alright. right?
             let _permit = semaphore.acquire().await.unwrap();
             read_with_uring(path, queue).await
-        });
+        };
+        #[cfg(feature = "io-uring")]
+        let spawn = tokio_uring::spawn(task);
+        #[cfg(not(feature = "io-uring"))]
+        let spawn = tokio::spawn(task);
         tasks.push(spawn);
     }
     for task in tasks {
@@ -268,17 +606,36 @@ pub async fn load_data_from_paths(paths: Vec<PathBuf>, config: ThrottleConfig) {
         }
     }
     let q = Arc::try_unwrap(queue).unwrap().into_inner();
-    TaskQueue::finish(q).await;
+    let (failures, fatal) = TaskQueue::finish(q).await;
+    for (path, err) in failures {
+        println!("Failed to insert {path}: {err:?}");
+    }
+    if let Some(err) = fatal {
+        println!("Fatal database error, aborting remaining inserts: {err:?}");
+    }
+}
+
+/// mtime (unix seconds) and size (bytes) for a file, used to detect changes
+/// between scans without reparsing every file's contents
+fn file_stamp(path: &PathBuf) -> Result<(i64, i64), io::Error> {
+    let metadata = std::fs::metadata(path)?;
+    let mtime = metadata
+        .modified()?
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs() as i64)
+        .unwrap_or(0);
+    Ok((mtime, metadata.len() as i64))
 }
 
 async fn read_with_uring(
     path: PathBuf,
     queue: Arc<tokio::sync::Mutex<TaskQueue>>,
 ) -> Result<(), Corruption> {
-    let file = File::open(&path)
+    let (mtime, size) = file_stamp(&path).map_err(|err| Corruption::io(path.to_owned(), 0, err))?;
+    let file = FileHandle::open(&path)
         .await
         .map_err(|err| Corruption::io(path.to_owned(), 0, err))?;
-    let mut reader = UringBufReader::new(file, path);
+    let mut reader = UringBufReader::new(file, path).with_file_size(size as u64);
     let bytes_read = reader.read_next(8196).await?;
 
     let marker: [u8; 4] = reader.get_bytes(4).await?.try_into().unwrap();
@@ -289,24 +646,69 @@ async fn read_with_uring(
                 return Err(Corruption {
                     path: reader.path.to_owned(),
                     message: "Not enough bytes for proper flac STREAMINFO.".to_owned(),
+                    cause: None,
                     file_cursor: reader.current_offset(),
                 });
             }
-            parse_flac(&mut reader).await?
+            Flac::parse(&mut reader, marker).await?
         }
         OGG_MARKER => {
             if bytes_read < 42 {
                 return Err(Corruption {
                     path: reader.path.to_owned(),
                     message: "Placeholder (figure out how much minima bytes ogg needs)".to_owned(),
+                    cause: None,
+                    file_cursor: reader.current_offset(),
+                });
+            }
+            OggOpus::parse(&mut reader, marker).await?
+        }
+        _ if marker[0..3] == ID3_MARKER => {
+            if bytes_read < 10 {
+                return Err(Corruption {
+                    path: reader.path.to_owned(),
+                    message: "Not enough bytes for an ID3v2 header.".to_owned(),
+                    cause: None,
                     file_cursor: reader.current_offset(),
                 });
             }
-            parse_ogg_pages(&mut reader).await?
+            Id3v2::parse(&mut reader, marker).await?
         }
         _ => return Ok(()),
     };
     reader.buf.clear();
+    let mut file_meta = file_meta;
+    file_meta.audio_file.mtime = Some(mtime);
+    file_meta.audio_file.size = Some(size);
+    file_meta.audio_file.audio_hash =
+        Some(hash_audio_payload(&reader, &file_meta, size as u64).await?);
     queue.lock().await.push(file_meta).await;
     Ok(())
 }
+
+/// Rescans `path`, only (re)parsing files whose mtime/size have changed
+/// since the last scan, and drops rows for files that disappeared. Modeled
+/// on polaris's REINDEX flow, this lets a long-running process re-sync a
+/// library on demand instead of wiping and rebuilding the whole DB.
+pub async fn reindex(path: &str, config: ThrottleConfig, pool: &SqlitePool) -> anyhow::Result<()> {
+    let all_paths = walk_dir(path);
+
+    let mut stale_paths = Vec::new();
+    let mut seen_paths = Vec::with_capacity(all_paths.len());
+    for file_path in all_paths {
+        let path_str = file_path.to_string_lossy().to_string();
+        let (mtime, size) = match file_stamp(&file_path) {
+            Ok(stamp) => stamp,
+            Err(_) => continue,
+        };
+        if AudioFile::is_stale(&path_str, mtime, size, pool).await? {
+            stale_paths.push(file_path);
+        }
+        seen_paths.push(path_str);
+    }
+
+    load_data_from_paths(stale_paths, config).await;
+    AudioFile::prune_missing(&seen_paths, pool).await?;
+
+    Ok(())
+}
@@ -1,27 +1,37 @@
 #![feature(string_from_utf8_lossy_owned)]
+mod cli;
 pub mod db;
 mod formats;
 mod io;
 pub mod queue;
+use clap::Parser;
+use cli::{Cli, Commands};
 use db::audio_file::AudioFile;
 use formats::opus_ogg::remove_comments;
 use io::{
+    backend::{FileBackend, FileHandle},
     ogg::OggPageReader,
-    reader::{load_data_from_paths, walk_dir, ThrottleConfig, UringBufReader},
+    reader::{load_data_from_paths, reindex, walk_dir, ThrottleConfig, UringBufReader},
 };
 use sqlx::SqlitePool;
 use std::{env, error::Error};
-use tokio_uring::fs::OpenOptions;
 
 fn main() -> Result<(), Box<dyn Error + Send + Sync>> {
     sysinfo::set_open_files_limit(10000);
+    if let Ok(Cli {
+        command: Some(Commands::Reindex { path }),
+    }) = Cli::try_parse()
+    {
+        return tokio_uring::start(async {
+            let pool = SqlitePool::connect("sqlite://dev.db").await?;
+            reindex(&path, ThrottleConfig::new(8), &pool).await
+        })
+        .map_err(Into::into);
+    }
     if env::args().last().unwrap() == "rehash" {
         let crazy_path = "./x/wheeler.opus".to_owned();
         tokio_uring::start(async {
-            let file = OpenOptions::new()
-                .write(true)
-                .read(true)
-                .open(&crazy_path)
+            let file = <FileHandle as FileBackend>::open(std::path::Path::new(&crazy_path))
                 .await
                 .unwrap();
             let mut reader = UringBufReader::new(file, crazy_path.into());
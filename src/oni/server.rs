@@ -8,6 +8,8 @@ use tonic::{
     Request, Response, Status,
 };
 
+use musicbrainz_db_client::SearchOutcome;
+
 use crate::cli::SearchService;
 use crate::oni::oni::{
     oni_control_server::{OniControl, OniControlServer},
@@ -34,8 +36,19 @@ impl OniControl for MyOniControl {
             SearchService::LocalMusicbrainz => {
                 let mut client = musicbrainz_db_client::create_client()
                     .await
-                    .expect("Hurr durr");
-                musicbrainz_db_client::search(&mut client, request.get_ref().query.clone()).await;
+                    .map_err(|err| Status::unavailable(err.to_string()))?;
+                let outcome =
+                    musicbrainz_db_client::search(&mut client, request.get_ref().query.clone(), 10)
+                        .await?;
+                match outcome {
+                    SearchOutcome::Success {
+                        releases,
+                        total_count,
+                        next_cursor,
+                    } => println!("{releases:?} (total {total_count}, next {next_cursor:?})"),
+                    SearchOutcome::Failure(message) => return Err(Status::unavailable(message)),
+                    SearchOutcome::Fatal(message) => return Err(Status::internal(message)),
+                }
             }
         }
 
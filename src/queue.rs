@@ -1,4 +1,7 @@
-use crate::db::vorbis::VorbisBlob;
+use crate::db::connect::{connect_with_backoff, is_transient_sqlx_error, BackoffConfig};
+use crate::db::cue_sheet::CueIndex;
+use crate::db::picture::VorbisBlob;
+use crate::db::seek_table::SeekPoint;
 use crate::db::{audio_file::AudioFileMeta, vorbis::VorbisComment};
 use futures::channel::{mpsc, mpsc::Sender};
 use futures::{SinkExt, StreamExt};
@@ -8,24 +11,46 @@ use tokio::task::JoinHandle;
 
 const QUEUE_LIMIT: usize = 25;
 
+/// Outcome of inserting one batch of `AudioFileMeta` into the database.
+#[derive(Debug)]
+pub enum BatchOutcome {
+    /// every item in the batch was inserted
+    Success,
+    /// the batch committed, but some individual files failed to insert and
+    /// were skipped; the queue keeps running
+    Failure(Vec<(String, sqlx::Error)>),
+    /// the transaction itself couldn't be committed, or the pool died; the
+    /// whole batch was lost and the executor stops
+    Fatal(sqlx::Error),
+}
+
 #[derive(Debug)]
 pub struct TaskQueue {
     queue: Vec<AudioFileMeta>,
     executor: JoinHandle<()>,
     sender: Sender<Option<Vec<AudioFileMeta>>>,
+    outcomes: mpsc::Receiver<BatchOutcome>,
 }
 
 impl TaskQueue {
     pub async fn new() -> Result<Self, sqlx::Error> {
         let (sender, mut receiver) = mpsc::channel::<Option<Vec<AudioFileMeta>>>(100);
-        let pool = SqlitePool::connect("sqlite://dev.db").await?;
+        let (mut outcome_sender, outcomes) = mpsc::channel::<BatchOutcome>(100);
+        let pool = connect_with_backoff(
+            BackoffConfig::default(),
+            || SqlitePool::connect("sqlite://dev.db"),
+            is_transient_sqlx_error,
+        )
+        .await?;
         let executor = tokio::spawn(async move {
             while let Some(queue) = receiver.next().await {
                 match queue {
                     Some(queue) => {
-                        if let Err(e) = TaskQueue::insert(queue, &pool).await {
-                            // Log errors somwhere here
-                            println!("Temporary log: {e:?}");
+                        let outcome = TaskQueue::insert(queue, &pool).await;
+                        let fatal = matches!(outcome, BatchOutcome::Fatal(_));
+                        let _ = outcome_sender.send(outcome).await;
+                        if fatal {
+                            break;
                         }
                     }
                     None => break,
@@ -36,44 +61,110 @@ impl TaskQueue {
             queue: Vec::with_capacity(QUEUE_LIMIT),
             executor,
             sender,
+            outcomes,
         })
     }
 
-    pub async fn finish(self) {
+    /// flushes any remaining items, waits for the executor to drain, and
+    /// returns every per-file failure plus the first fatal error seen, if any
+    pub async fn finish(self) -> (Vec<(String, sqlx::Error)>, Option<sqlx::Error>) {
         let mut sender = self.sender;
+        let mut outcomes = self.outcomes;
         if !self.queue.is_empty() {
             let _ = sender.send(Some(self.queue)).await;
         }
         let _ = sender.send(None).await;
         let _ = self.executor.await;
+
+        let mut failures = Vec::new();
+        let mut fatal = None;
+        outcomes.close();
+        while let Some(outcome) = outcomes.next().await {
+            match outcome {
+                BatchOutcome::Success => {}
+                BatchOutcome::Failure(mut batch_failures) => failures.append(&mut batch_failures),
+                BatchOutcome::Fatal(err) => fatal.get_or_insert(err),
+            };
+        }
+        (failures, fatal)
     }
-    pub async fn insert(queue: Vec<AudioFileMeta>, pool: &Pool<Sqlite>) -> Result<(), sqlx::Error> {
-        let mut transaction = pool.begin().await?;
+
+    /// inserts a batch in its own transaction, one item per nested
+    /// SAVEPOINT. A file that fails to insert has its savepoint rolled back
+    /// (dropping it without committing is enough) and is recorded and
+    /// skipped, rather than leaving its partial writes staged in the batch
+    /// transaction; only a failure to begin/commit a transaction or
+    /// savepoint is treated as fatal.
+    pub async fn insert(queue: Vec<AudioFileMeta>, pool: &Pool<Sqlite>) -> BatchOutcome {
+        let mut transaction = match pool.begin().await {
+            Ok(transaction) => transaction,
+            Err(err) => return BatchOutcome::Fatal(err),
+        };
+
+        let mut failures = Vec::new();
         for item in queue {
-            let file_id = item.audio_file.insert(&mut *transaction).await?;
-            for blob in item.blobs {
-                if VorbisBlob::hash_exists(blob.hash.clone(), &mut *transaction).await? {
-                    continue;
+            let path = item.audio_file.path.clone();
+            let mut savepoint = match transaction.begin().await {
+                Ok(savepoint) => savepoint,
+                Err(err) => return BatchOutcome::Fatal(err),
+            };
+
+            match TaskQueue::insert_one(item, &mut savepoint).await {
+                Ok(()) => {
+                    if let Err(err) = savepoint.commit().await {
+                        return BatchOutcome::Fatal(err);
+                    }
                 }
-                blob.insert(&mut *transaction).await?;
+                Err(err) => failures.push((path, err)),
             }
-            for (mut vorbis_meta, vorbis) in item.comments {
-                vorbis_meta.file_id = Some(file_id);
-                let meta_id = vorbis_meta.insert(&mut *transaction).await?;
-                if vorbis.is_empty() {
-                    continue;
-                }
-                VorbisComment::insert_many(meta_id, vorbis, &mut *transaction).await?;
+        }
+
+        match transaction.commit().await {
+            Ok(()) if failures.is_empty() => BatchOutcome::Success,
+            Ok(()) => BatchOutcome::Failure(failures),
+            Err(err) => BatchOutcome::Fatal(err),
+        }
+    }
+
+    async fn insert_one(
+        item: AudioFileMeta,
+        transaction: &mut sqlx::Transaction<'_, Sqlite>,
+    ) -> Result<(), sqlx::Error> {
+        let file_id = item.audio_file.insert(&mut **transaction).await?;
+        for blob in item.blobs {
+            if VorbisBlob::hash_exists(blob.hash.clone(), &mut **transaction).await? {
+                continue;
             }
-            for picture in item.pictures {
-                picture.insert(file_id, &mut *transaction).await?;
+            blob.insert(&mut **transaction).await?;
+        }
+        for (mut vorbis_meta, vorbis) in item.comments {
+            vorbis_meta.file_id = Some(file_id);
+            let meta_id = vorbis_meta.insert(&mut **transaction).await?;
+            if vorbis.is_empty() {
+                continue;
             }
-            for padding in item.paddings {
-                padding.insert(file_id, &mut *transaction).await?;
+            VorbisComment::insert_many(meta_id, vorbis, &mut **transaction).await?;
+        }
+        for picture in item.pictures {
+            picture.insert(file_id, &mut **transaction).await?;
+        }
+        for padding in item.paddings {
+            padding.insert(file_id, &mut **transaction).await?;
+        }
+        if let Some(stream_info) = item.stream_info {
+            stream_info.insert(file_id, &mut **transaction).await?;
+        }
+        SeekPoint::insert_many(file_id, item.seek_points, &mut **transaction).await?;
+        if let Some((sheet, tracks)) = item.cue_sheet {
+            let sheet_id = sheet.insert(file_id, &mut **transaction).await?;
+            for (track, indices) in tracks {
+                let track_id = track.insert(sheet_id, &mut **transaction).await?;
+                CueIndex::insert_many(track_id, indices, &mut **transaction).await?;
             }
         }
-        transaction.commit().await
+        Ok(())
     }
+
     pub async fn push(&mut self, item: AudioFileMeta) {
         self.queue.push(item);
         if self.queue.len() >= QUEUE_LIMIT {
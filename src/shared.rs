@@ -1,27 +1,4 @@
-use std::collections::HashMap;
-
-use anyhow::anyhow;
-
 use crate::reader::UringBufReader;
-//use anyhow::anyhow;
-
-pub const VORBIS_FIELDS_LOWER: [&str; 15] = [
-    "title",
-    "version",
-    "album",
-    "tracknumber",
-    "artist",
-    "performer",
-    "copyright",
-    "license",
-    "organization",
-    "description",
-    "genre",
-    "date",
-    "location",
-    "contact",
-    "isrc",
-];
 
 pub const FLAC_MARKER: [u8; 4] = [0x66, 0x4C, 0x61, 0x43];
 pub const OGG_MARKER: [u8; 4] = [0x4F, 0x67, 0x67, 0x53];
@@ -29,79 +6,60 @@ pub const OGG_MARKER: [u8; 4] = [0x4F, 0x67, 0x67, 0x53];
 #[derive(Debug, Clone)]
 pub struct MusicFile {
     pub path: String,
-    pub comments: Vec<VorbisComment>,
+    pub comments: VorbisComment,
     pub pictures: Vec<Picture>,
 }
 
-#[derive(Debug, Clone)]
+/// An ordered, duplicate-preserving Vorbis comment list.
+///
+/// The Vorbis spec allows a field name to repeat (multiple `ARTIST=` or
+/// `GENRE=` lines are both valid and meaningful), so comments are kept as
+/// an ordered `Vec<(String, String)>` rather than collapsed into fixed
+/// struct fields or a map that would silently drop duplicates.
+#[derive(Debug, Clone, Default)]
 pub struct VorbisComment {
     pub vendor: String,
-    pub title: String,
-    pub version: String,
-    pub album: String,
-    pub tracknumber: String,
-    pub artist: String,
-    pub performer: String,
-    pub copyright: String,
-    pub license: String,
-    pub organization: String,
-    pub description: String,
-    pub genre: String,
-    pub date: String,
-    pub location: String,
-    pub contact: String,
-    pub isrc: String,
-    pub outcast: String,
+    pub items: Vec<(String, String)>,
 }
+
 impl VorbisComment {
-    pub fn init(map: HashMap<String, String>, outcasts: Vec<String>) -> Self {
-        let outcast = outcasts.join("|||");
-        let vendor = map.get("vendor").map_or(String::new(), |v| v.to_string());
-        let contact = map.get("contact").map_or(String::new(), |v| v.to_string());
-        let location = map.get("location").map_or(String::new(), |v| v.to_string());
-        let date = map.get("date").map_or(String::new(), |v| v.to_string());
-        let genre = map.get("genre").map_or(String::new(), |v| v.to_string());
-        let isrc = map.get("isrc").map_or(String::new(), |v| v.to_string());
-        let album = map.get("album").map_or(String::new(), |v| v.to_string());
-        let version = map.get("version").map_or(String::new(), |v| v.to_string());
-        let title = map.get("title").map_or(String::new(), |v| v.to_string());
-        let description = map
-            .get("description")
-            .map_or(String::new(), |v| v.to_string());
-        let organization = map
-            .get("organization")
-            .map_or(String::new(), |v| v.to_string());
-        let license = map.get("license").map_or(String::new(), |v| v.to_string());
-        let copyright = map
-            .get("copyright")
-            .map_or(String::new(), |v| v.to_string());
-        let performer = map
-            .get("performer")
-            .map_or(String::new(), |v| v.to_string());
-        let artist = map.get("artist").map_or(String::new(), |v| v.to_string());
-        let tracknumber = map
-            .get("tracknumber")
-            .map_or(String::new(), |v| v.to_string());
+    pub fn new(vendor: String, items: Vec<(String, String)>) -> Self {
+        Self { vendor, items }
+    }
 
-        VorbisComment {
-            title,
-            vendor,
-            description,
-            version,
-            album,
-            date,
-            isrc,
-            genre,
-            artist,
-            license,
-            contact,
-            location,
-            performer,
-            copyright,
-            tracknumber,
-            organization,
-            outcast,
-        }
+    /// first value stored for `key`, matched case-insensitively
+    pub fn get(&self, key: &str) -> Option<&str> {
+        self.items
+            .iter()
+            .find(|(k, _)| k.eq_ignore_ascii_case(key))
+            .map(|(_, v)| v.as_str())
+    }
+
+    /// every value stored for `key`, in file order
+    pub fn get_all<'a>(&'a self, key: &'a str) -> impl Iterator<Item = &'a str> {
+        self.items
+            .iter()
+            .filter(move |(k, _)| k.eq_ignore_ascii_case(key))
+            .map(|(_, v)| v.as_str())
+    }
+
+    pub fn title(&self) -> Option<&str> {
+        self.get("title")
+    }
+    pub fn artist(&self) -> Option<&str> {
+        self.get("artist")
+    }
+    pub fn album(&self) -> Option<&str> {
+        self.get("album")
+    }
+    pub fn tracknumber(&self) -> Option<&str> {
+        self.get("tracknumber")
+    }
+    pub fn genre(&self) -> Option<&str> {
+        self.get("genre")
+    }
+    pub fn date(&self) -> Option<&str> {
+        self.get("date")
     }
 }
 
@@ -119,98 +77,40 @@ pub struct Picture {
 }
 
 pub async fn parse_vorbis(vorbis_block: &[u8]) -> anyhow::Result<VorbisComment> {
-    let mut comments = HashMap::new();
-    let mut outcasts = Vec::new();
     let block_length = vorbis_block.len();
     let vendor_end = 4 + u32::from_le_bytes(vorbis_block[0..4].try_into()?) as usize;
-    comments.insert(
-        "vendor".to_string(),
-        String::from_utf8_lossy(&vorbis_block[4..vendor_end]).to_string(),
-    );
+    let vendor = String::from_utf8_lossy(&vorbis_block[4..vendor_end]).to_string();
+
     let comment_list_len =
         u32::from_le_bytes(vorbis_block[vendor_end..vendor_end + 4].try_into()?) as usize;
-    let first_comment_len =
-        u32::from_le_bytes(vorbis_block[vendor_end + 4..vendor_end + 8].try_into()?) as usize;
-    if comment_list_len > block_length {
-        return Err(anyhow!("Comment list len > block length"));
-    } else if first_comment_len > block_length {
-        let mut comment_cursor = vendor_end;
-        while comment_cursor < block_length {
-            let comment_len =
-                u32::from_le_bytes(vorbis_block[comment_cursor..comment_cursor + 4].try_into()?)
-                    as usize;
-            comment_cursor += 4;
-            if comment_cursor + comment_len >= block_length {
-                break;
-            }
-            let comment = String::from_utf8_lossy(
-                &vorbis_block[comment_cursor..comment_cursor + comment_len],
-            )
-            .to_lowercase();
-            match &comment.split_once('=') {
-                Some((key, val)) => {
-                    if VORBIS_FIELDS_LOWER.contains(key) {
-                        comments.insert(key.to_lowercase(), val.to_string());
-                        comment_cursor += comment_len;
-                    } else {
-                        outcasts.push(comment);
-                        comment_cursor += comment_len;
-                        continue;
-                    }
-                }
-                None => {
-                    println!("corrupted comment {comment:?}");
-                    continue;
-                    //return Err(anyhow!("Corrupted comment: {comment}"));
-                    // skip the corrupted comments for now
-                }
-            };
+
+    let mut items = Vec::with_capacity(comment_list_len);
+    let mut comment_cursor = vendor_end + 4;
+    for _ in 0..comment_list_len {
+        if comment_cursor + 4 > block_length {
+            break;
         }
-    } else {
-        let mut comment_cursor = vendor_end + 4;
-        for _ in 1..=comment_list_len {
-            if comment_cursor + 4 >= block_length {
-                break;
-            }
-            let comment_len =
-                u32::from_le_bytes(vorbis_block[comment_cursor..4 + comment_cursor].try_into()?)
-                    as usize;
+        let comment_len =
+            u32::from_le_bytes(vorbis_block[comment_cursor..comment_cursor + 4].try_into()?)
+                as usize;
+        comment_cursor += 4;
+        if comment_cursor + comment_len > block_length {
+            break;
+        }
+        let comment = String::from_utf8_lossy(
+            &vorbis_block[comment_cursor..comment_cursor + comment_len],
+        )
+        .to_string();
+        comment_cursor += comment_len;
 
-            //let z = String::from_utf8_lossy(&vorbis_block[comment_cursor..]);
-            //println!("{z:?}");
-            if comment_len + comment_cursor >= block_length {
-                //println!("{comment_len} + {comment_cursor} > {block_length}");
-                continue;
-                // skip any corrupted comment lengths
-                //return Err(anyhow!(
-                //    "Corrupted comment length: {comment_len} > {block_length}"
-                //));
+        match comment.split_once('=') {
+            Some((key, val)) => items.push((key.to_string(), val.to_string())),
+            None => {
+                println!("corrupted comment {comment:?}");
+                // skip the corrupted comments for now
             }
-            comment_cursor += 4;
-            let comment = String::from_utf8_lossy(
-                &vorbis_block[comment_cursor..comment_cursor + comment_len],
-            )
-            .to_lowercase();
-            match &comment.split_once('=') {
-                Some((key, val)) => {
-                    if VORBIS_FIELDS_LOWER.contains(key) {
-                        comments.insert(key.to_lowercase(), val.to_string());
-                    } else {
-                        outcasts.push(comment);
-                        comment_cursor += comment_len;
-                        continue;
-                    }
-                }
-                None => {
-                    println!("corrupted comment {comment:?}");
-                    continue;
-                    //return Err(anyhow!("Corrupted comment: {comment}"));
-                    // skip the corrupted comments for now
-                }
-            };
-
-            comment_cursor += comment_len;
         }
     }
-    Ok(VorbisComment::init(comments, outcasts))
+
+    Ok(VorbisComment::new(vendor, items))
 }